@@ -0,0 +1,212 @@
+use simjson::JsonData;
+use simweb::json_encode;
+
+pub const DEFAULT_DIGITS: u32 = 6;
+/// RFC 4226 requires at least 6 digits, a 31-bit truncated HMAC value has up to 10 digits
+pub const MIN_DIGITS: u32 = 6;
+pub const MAX_DIGITS: u32 = 10;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+/// HMAC hash function used for codes generation.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Parses a name as it's used in otpauth URIs, the case is ignored.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA1" => Some(Algorithm::Sha1),
+            "SHA256" => Some(Algorithm::Sha256),
+            "SHA512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        }
+    }
+}
+
+/// Presentation of a code.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    /// decimal digits
+    #[default]
+    Decimal,
+    /// 5 symbols of the Steam Guard alphabet
+    Steam,
+}
+
+impl Encoding {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "decimal" => Some(Encoding::Decimal),
+            "steam" => Some(Encoding::Steam),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Decimal => "decimal",
+            Encoding::Steam => "steam",
+        }
+    }
+}
+
+/// An account record as it's stored in the DB.
+#[derive(Debug, Clone)]
+pub struct Account {
+    /// base32 encoded secret
+    pub secret: String,
+    /// the moving factor of an event based (HOTP) account, `None` for a time based one
+    pub counter: Option<u64>,
+    pub digits: u32,
+    /// the time step in seconds
+    pub period: u64,
+    pub algorithm: Algorithm,
+    pub encoding: Encoding,
+    /// Unix time to start counting time steps from
+    pub t0: u64,
+    /// the time step or the HOTP counter of the last verified code, it protects from a code replay
+    pub last_used: Option<u64>,
+    /// the provider name, as it came in an otpauth URI
+    pub issuer: Option<String>,
+    /// emergency codes of Google Authenticator PAM module file
+    pub scratch_codes: Vec<String>,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            secret: String::new(),
+            counter: None,
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            algorithm: Algorithm::default(),
+            encoding: Encoding::default(),
+            t0: 0,
+            last_used: None,
+            issuer: None,
+            scratch_codes: Vec::new(),
+        }
+    }
+}
+
+impl Account {
+    /// Creates a TOTP account with the default parameters.
+    pub fn new(secret: String) -> Self {
+        Account { secret, ..Default::default() }
+    }
+
+    /// Checks that both accounts generate the same codes, the replay protection state
+    /// and descriptive values don't matter.
+    pub fn same_codes(&self, other: &Account) -> bool {
+        self.secret == other.secret && self.counter == other.counter && self.digits == other.digits &&
+            self.period == other.period && self.algorithm == other.algorithm && self.encoding == other.encoding &&
+            self.t0 == other.t0
+    }
+
+    /// Restores an account from the DB JSON, a plain string is a secret of a TOTP account
+    /// with the default parameters. The error tells which value isn't supported, such an account
+    /// can't be skipped, since the next write of the DB would lose it.
+    pub fn from_json(value: &JsonData) -> Result<Self, String> {
+        match value {
+            JsonData::Text(secret) => Ok(Account::new(secret.to_string())),
+            JsonData::Data(fields) => {
+                let Some(JsonData::Text(secret)) = fields.get("secret") else {
+                    return Err("no secret".to_string())
+                };
+                let num = |name| match fields.get(name) {
+                    Some(JsonData::Num(num)) => Some(*num as u64),
+                    _ => None,
+                };
+                let mut res = Account::new(secret.to_string());
+                res.counter = num("counter");
+                if let Some(digits) = num("digits") {
+                    res.digits = Some(digits).filter(|digits| (MIN_DIGITS as u64..=MAX_DIGITS as u64).contains(digits))
+                        .ok_or_else(|| format!("{digits} digits aren't supported"))? as u32
+                }
+                if let Some(period) = num("period") {
+                    res.period = Some(period).filter(|period| *period > 0).ok_or_else(|| format!("invalid period {period}"))?
+                }
+                if let Some(JsonData::Text(algorithm)) = fields.get("algorithm") {
+                    res.algorithm = Algorithm::parse(algorithm).ok_or_else(|| format!("{algorithm} algorithm isn't supported"))?
+                }
+                if let Some(JsonData::Text(encoding)) = fields.get("encoding") {
+                    res.encoding = Encoding::parse(encoding).ok_or_else(|| format!("unsupported encoding '{encoding}'"))?
+                }
+                if let Some(t0) = num("t0") {
+                    res.t0 = t0
+                }
+                res.last_used = num("last_used");
+                if let Some(JsonData::Text(issuer)) = fields.get("issuer") {
+                    res.issuer = Some(issuer.to_string())
+                }
+                if let Some(JsonData::Arr(codes)) = fields.get("scratch_codes") {
+                    res.scratch_codes = codes.iter().filter_map(|code| match code {
+                        JsonData::Text(code) => Some(code.to_string()),
+                        _ => None,
+                    }).collect()
+                }
+                Ok(res)
+            }
+            _ => Err("not an account".to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut res = format!(r#"{{"secret":"{}","digits":{},"period":{},"algorithm":"{}","encoding":"{}","t0":{}"#,
+            json_encode(&self.secret), self.digits, self.period, self.algorithm.name(), self.encoding.name(), self.t0);
+        if let Some(counter) = self.counter {
+            res.push_str(&format!(r#","counter":{counter}"#));
+        }
+        if let Some(last_used) = self.last_used {
+            res.push_str(&format!(r#","last_used":{last_used}"#));
+        }
+        if let Some(issuer) = &self.issuer {
+            res.push_str(&format!(r#","issuer":"{}""#, json_encode(issuer)));
+        }
+        if !self.scratch_codes.is_empty() {
+            res.push_str(&format!(r#","scratch_codes":[{}]"#,
+                self.scratch_codes.iter().map(|code| format!(r#""{}""#, json_encode(code))).collect::<Vec<_>>().join(",")));
+        }
+        res.push('}');
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let account = Account { counter: Some(7), digits: 8, period: 60, t0: 100, ..Account::new("JBSWY3DPEHPK3PXP".to_string()) };
+        let json = account.to_json();
+        let read = Account::from_json(&simjson::parse(&json)).unwrap();
+        assert_eq!(read.to_json(), json);
+        assert_eq!((read.counter, read.digits, read.period, read.t0), (Some(7), 8, 60, 100));
+        // a plain secret is a TOTP account with the default parameters
+        let read = Account::from_json(&simjson::parse(r#""JBSWY3DPEHPK3PXP""#)).unwrap();
+        assert_eq!(read.to_json(), Account::new("JBSWY3DPEHPK3PXP".to_string()).to_json());
+    }
+
+    #[test]
+    fn unsupported_json() {
+        for (json, reason) in [(r#"{"digits":6}"#, "no secret"), (r#"{"secret":"JBSWY3DP","digits":12}"#, "12 digits aren't supported"),
+            (r#"{"secret":"JBSWY3DP","period":0}"#, "invalid period 0"), (r#"{"secret":"JBSWY3DP","algorithm":"MD5"}"#, "MD5 algorithm isn't supported"),
+            (r#"{"secret":"JBSWY3DP","encoding":"base64"}"#, "unsupported encoding 'base64'"), ("7", "not an account")] {
+            assert_eq!(Account::from_json(&simjson::parse(json)).err().as_deref(), Some(reason));
+        }
+    }
+}
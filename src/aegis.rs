@@ -0,0 +1,179 @@
+/*
+Aegis Authenticator vault export
+
+    {
+        "version": 1,
+        "header": {
+            "slots": [              // null for a plain export
+                {
+                    "type": 1,      // 0 - raw key, 1 - password, 2 - biometric
+                    "key": HEX,     // the master key encrypted by AES-256-GCM with the slot key
+                    "key_params": {"nonce": HEX, "tag": HEX},
+                    "n": 32768, "r": 8, "p": 1, "salt": HEX     // scrypt of the password gives the slot key
+                }
+            ],
+            "params": {"nonce": HEX, "tag": HEX}    // of the db encrypted by the master key, null for a plain export
+        },
+        "db": DB                    // an object for a plain export, base64 of the encrypted DB JSON otherwise
+    }
+
+    DB:
+        {
+            "version": 3,
+            "entries": [
+                {
+                    "type": "totp",     // totp, hotp, steam, motp, yandex
+                    "name": "account", "issuer": "provider",
+                    "groups": [UUID],   // version 3, older ones have "group": "group name"
+                    "info": {"secret": BASE32, "algo": "SHA1", "digits": 6, "period": 30, "counter": 0}
+                }
+            ],
+            "groups": [{"uuid": UUID, "name": "group name"}]
+        }
+*/
+use crate::{TOTPError, aes256gcm, scrypt::scrypt, migration::base64_decode,
+    import::{self, Entry, Importer, Object, text, num, object, array, objects}, account::Account};
+use simjson::JsonData;
+use std::collections::HashMap;
+
+const PASSWORD_SLOT: f64 = 1.0;
+
+pub struct Aegis;
+
+impl Importer for Aegis {
+    fn format(&self) -> &'static str {
+        "Aegis"
+    }
+
+    fn recognize(&self, json: &JsonData) -> bool {
+        matches!(json, JsonData::Data(vault) if object(vault, "header").is_some() && vault.contains_key("db"))
+    }
+
+    fn entries(&self, json: &JsonData, password: &str) -> Result<Vec<Entry>, TOTPError> {
+        let JsonData::Data(vault) = json else {
+            return Err(TOTPError::new("not an Aegis vault"))
+        };
+        let db = match vault.get("db") {
+            Some(JsonData::Data(db)) => db.clone(),
+            Some(JsonData::Text(encrypted)) => {
+                let header = object(vault, "header").ok_or_else(|| TOTPError::new("no vault header"))?;
+                let plain = decrypt(header, encrypted, password)?;
+                match simjson::parse(&String::from_utf8_lossy(&plain)) {
+                    JsonData::Data(db) => db,
+                    _ => return Err(TOTPError::new("the decrypted vault isn't JSON")),
+                }
+            }
+            _ => return Err(TOTPError::new("no vault db")),
+        };
+        if num(&db, "version").is_some_and(|version| version > 3.0) {
+            return Err(TOTPError::new("the vault version isn't supported"))
+        }
+        let group_names: HashMap<&str, &str> = objects(array(&db, "groups"))
+            .filter_map(|group| Some((text(group, "uuid")?, text(group, "name")?))).collect();
+        Ok(objects(array(&db, "entries")).map(|entry| parse_entry(entry, &group_names)).collect())
+    }
+}
+
+/// Decrypts the master key by a password slot and the db by the master key.
+fn decrypt(header: &Object, encrypted: &str, password: &str) -> Result<Vec<u8>, TOTPError> {
+    let slots: Vec<_> = objects(array(header, "slots")).filter(|slot| num(slot, "type") == Some(PASSWORD_SLOT)).collect();
+    if slots.is_empty() {
+        return Err(TOTPError::new("the vault has no password slot"))
+    }
+    if password.is_empty() {
+        return Err(TOTPError::new("the vault is encrypted, a password is required"))
+    }
+    let mut master_key = None;
+    for slot in slots {
+        let hex = |name| text(slot, name).and_then(hex_decode).ok_or_else(|| TOTPError::new(format!("invalid slot {name}")));
+        let (salt, key) = (hex("salt")?, hex("key")?);
+        let params = object(slot, "key_params").ok_or_else(|| TOTPError::new("no slot key parameters"))?;
+        let (nonce, tag) = nonce_and_tag(params)?;
+        let cost = |name| num(slot, name).filter(|&value| value >= 1.0).map(|value| value as u64)
+            .ok_or_else(|| TOTPError::new(format!("invalid scrypt {name}")));
+        let block_cost = |name| u32::try_from(cost(name)?).map_err(|_| TOTPError::new(format!("invalid scrypt {name}")));
+        let mut slot_key = [0u8; aes256gcm::KEY_LEN];
+        scrypt(password.as_bytes(), &salt, cost("n")?, block_cost("r")?, block_cost("p")?, &mut slot_key)?;
+        if let Some(key) = aes256gcm::open(&slot_key, &nonce, &[], &[key, tag].concat()) &&
+            let Ok(key) = key.try_into() {
+            master_key = Some(key);
+            break
+        }
+    }
+    let master_key = master_key.ok_or_else(|| TOTPError::new("wrong vault password"))?;
+    let params = object(header, "params").ok_or_else(|| TOTPError::new("no vault encryption parameters"))?;
+    let (nonce, tag) = nonce_and_tag(params)?;
+    let mut sealed = base64_decode(encrypted).ok_or_else(|| TOTPError::new("the vault db isn't valid base64"))?;
+    sealed.extend_from_slice(&tag);
+    aes256gcm::open(&master_key, &nonce, &[], &sealed).ok_or_else(|| TOTPError::new("the vault db is corrupted"))
+}
+
+fn nonce_and_tag(params: &Object) -> Result<([u8; aes256gcm::NONCE_LEN], Vec<u8>), TOTPError> {
+    let nonce = text(params, "nonce").and_then(hex_decode).and_then(|nonce| nonce.try_into().ok())
+        .ok_or_else(|| TOTPError::new("invalid nonce"))?;
+    let tag = text(params, "tag").and_then(hex_decode).filter(|tag| tag.len() == aes256gcm::TAG_LEN)
+        .ok_or_else(|| TOTPError::new("invalid tag"))?;
+    Ok((nonce, tag))
+}
+
+fn parse_entry(entry: &Object, group_names: &HashMap<&str, &str>) -> Entry {
+    let mut groups: Vec<String> = array(entry, "groups").iter().filter_map(|uuid| match uuid {
+        JsonData::Text(uuid) => group_names.get(uuid.as_str()).map(|name| name.to_string()),
+        _ => None,
+    }).collect();
+    if let Some(group) = text(entry, "group") {
+        groups.push(group.to_string())
+    }
+    let issuer = text(entry, "issuer").map(str::to_string);
+    let name = text(entry, "name").unwrap_or_default().to_string();
+    let account = match object(entry, "info") {
+        Some(info) => import::account(text(entry, "type").unwrap_or("totp"), text(info, "secret").unwrap_or_default(),
+            text(info, "algo"), num(info, "digits"), num(info, "period"), num(info, "counter")),
+        None => Err("no OTP info".to_string()),
+    }.map(|account| Account { issuer: issuer.clone(), ..account });
+    Entry { groups, issuer, name, account }
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None
+    }
+    (0..hex.len()).step_by(2).map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{Algorithm, Encoding};
+
+    const PLAIN: &str = r#"{"version":1,"header":{"slots":null,"params":null},"db":{"version":3,"entries":[{"type":"totp","name":"john","issuer":"GitHub","groups":["1f6e"],"info":{"secret":"JBSWY3DPEHPK3PXP","algo":"SHA256","digits":8,"period":60}},{"type":"hotp","name":"jane","issuer":"","info":{"secret":"gezd gnbv gy3t qojq","algo":"SHA1","digits":6,"counter":5}},{"type":"steam","name":"gamer","issuer":"Steam","info":{"secret":"MFRGGZDFMZTWQ2LK","algo":"SHA1","digits":5,"period":30}},{"type":"motp","name":"pin","info":{"secret":"ABCD","pin":"1234"}}],"groups":[{"uuid":"1f6e","name":"Work"}]}}"#;
+    /// the vault of one entry of GitHub john encrypted by the password "test", scrypt N is lowered to 1024
+    const ENCRYPTED: &str = r#"{"version":1,"header":{"slots":[{"type":1,"uuid":"s","key":"f1d8cbf8b77c4fc7558d9861ca86c701485a1f813c35652435a28e2df9480077","key_params":{"nonce":"000102030405060708090a0b","tag":"12db033ec99fcda7b25e98da9ef949a3"},"n":1024,"r":8,"p":1,"salt":"6465666768696a6b6c6d6e6f707172737475767778797a7b7c7d7e7f80818283"}],"params":{"nonce":"1415161718191a1b1c1d1e1f","tag":"edbbcbddfb6d83b78317953d1509f51c"}},"db":"QfzEfmwckj/ohTACGWZ0ToV1TmxnvkZNkrKNKVtCkRV4KWqQwe8xaAo7Ln515zcqXG4PdqFy/W3dmZ+myKp35K6ZmjUXRLnZA907Tlnr0+fiJUVkEbY1MFSL1MCdThqbItIPzJnY2eUAsptQjo7Z41Ndh+tizS/3z1p3XW3L+W1XioGP5NJ7Oh5w9ucE3wvCwBS29PtpHfhJRbdg2YQt"}"#;
+
+    #[test]
+    fn plain_vault() {
+        let entries = Aegis.entries(&simjson::parse(PLAIN), "").unwrap();
+        let [john, jane, gamer, pin] = &entries[..] else { panic!("4 entries expected") };
+        assert_eq!((john.groups.as_slice(), john.issuer.as_deref(), john.name.as_str()), (&["Work".to_string()][..], Some("GitHub"), "john"));
+        let account = john.account.as_ref().unwrap();
+        assert_eq!((account.algorithm, account.digits, account.period), (Algorithm::Sha256, 8, 60));
+        assert_eq!((jane.issuer.as_deref(), jane.account.as_ref().map(|account| (account.secret.as_str(), account.counter))),
+            (None, Ok(("GEZDGNBVGY3TQOJQ", Some(5)))));
+        assert_eq!(gamer.account.as_ref().unwrap().encoding, Encoding::Steam);
+        assert_eq!(pin.account.as_ref().err().map(String::as_str), Some("motp OTP type isn't supported"));
+    }
+
+    #[test]
+    fn encrypted_vault() {
+        let entries = Aegis.entries(&simjson::parse(ENCRYPTED), "test").unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.account.as_ref().unwrap().secret.as_str()).collect::<Vec<_>>(), ["JBSWY3DPEHPK3PXP"]);
+        for (password, cause) in [("", "the vault is encrypted, a password is required"), ("Test", "wrong vault password")] {
+            assert_eq!(Aegis.entries(&simjson::parse(ENCRYPTED), password).err().unwrap().cause, cause);
+        }
+        let tampered = ENCRYPTED.replace(r#""tag":"edbb"#, r#""tag":"eebb"#);
+        assert_eq!(Aegis.entries(&simjson::parse(&tampered), "test").err().unwrap().cause, "the vault db is corrupted");
+        // r of 2^32 + 8 isn't taken as 8
+        let costly = ENCRYPTED.replace(r#""r":8"#, r#""r":4294967304"#);
+        assert_eq!(Aegis.entries(&simjson::parse(&costly), "test").err().unwrap().cause, "invalid scrypt r");
+    }
+}
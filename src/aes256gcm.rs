@@ -0,0 +1,196 @@
+/*
+AES-256 in Galois/Counter Mode as described in FIPS 197 and NIST SP 800-38D
+
+    H = AES(K, 0^128)
+    J0 = IV | 0^31 | 1                  // 96 bits IV
+    C = plaintext xor AES(K, inc32(J0)), AES(K, inc32(inc32(J0))), ...
+    S = GHASH_H(A | pad16(A) | C | pad16(C) | len64(A) | len64(C))     // lengths in bits, big-endian
+    T = AES(K, J0) xor S
+
+    GHASH multiplies in GF(2^128) by x^128 + x^7 + x^2 + x + 1 with the reflected bit order,
+    the first bit of a block is the coefficient of x^0
+
+Only decryption is needed, so only the forward cipher is implemented.
+*/
+use crate::constant_time_eq;
+use std::convert::TryInto;
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+const ROUNDS: usize = 14;
+
+/// Verifies the authentication tag and decrypts the data, `None` is returned
+/// when the data were tampered or the key is wrong.
+pub fn open(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < TAG_LEN {
+        return None
+    }
+    let (cipher, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    let aes = Aes256::new(key);
+    let mut counter = [0u8; 16];
+    counter[..NONCE_LEN].copy_from_slice(nonce);
+    counter[15] = 1;
+
+    let h = u128::from_be_bytes(aes.encrypt(&[0; 16]));
+    let mut s = 0u128;
+    for data in [aad, cipher] {
+        for block in data.chunks(16) {
+            let mut padded = [0u8; 16];
+            padded[..block.len()].copy_from_slice(block);
+            s = gf_multiply(s ^ u128::from_be_bytes(padded), h)
+        }
+    }
+    s = gf_multiply(s ^ (((aad.len() as u128 * 8) << 64) | (cipher.len() as u128 * 8)), h);
+    let expected = (s ^ u128::from_be_bytes(aes.encrypt(&counter))).to_be_bytes();
+    if !constant_time_eq(&expected, tag) {
+        return None
+    }
+
+    let mut res = cipher.to_vec();
+    for chunk in res.chunks_mut(16) {
+        let next = u32::from_be_bytes(counter[12..].try_into().unwrap()).wrapping_add(1);
+        counter[12..].copy_from_slice(&next.to_be_bytes());
+        for (byte, key) in chunk.iter_mut().zip(aes.encrypt(&counter)) {
+            *byte ^= key
+        }
+    }
+    Some(res)
+}
+
+/// Multiplication of GHASH blocks as numbers of the big-endian byte order.
+fn gf_multiply(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let (mut z, mut v) = (0u128, y);
+    for i in 0..128 {
+        if x >> (127 - i) & 1 == 1 {
+            z ^= v
+        }
+        v = if v & 1 == 1 { v >> 1 ^ R } else { v >> 1 }
+    }
+    z
+}
+
+struct Aes256 {
+    round_keys: [[u8; 16]; ROUNDS + 1],
+    sbox: [u8; 256],
+}
+
+impl Aes256 {
+    fn new(key: &[u8; KEY_LEN]) -> Self {
+        let sbox = sbox();
+        let mut words = [[0u8; 4]; 4 * (ROUNDS + 1)];
+        for (word, key) in words.iter_mut().zip(key.chunks(4)) {
+            word.copy_from_slice(key)
+        }
+        let mut rcon = 1u8;
+        for i in 8..words.len() {
+            let mut temp = words[i - 1];
+            if i % 8 == 0 {
+                temp = [sbox[temp[1] as usize] ^ rcon, sbox[temp[2] as usize], sbox[temp[3] as usize], sbox[temp[0] as usize]];
+                rcon = xtime(rcon)
+            } else if i % 8 == 4 {
+                temp = temp.map(|b| sbox[b as usize])
+            }
+            for k in 0..4 {
+                words[i][k] = words[i - 8][k] ^ temp[k]
+            }
+        }
+        let mut round_keys = [[0u8; 16]; ROUNDS + 1];
+        for (round_key, words) in round_keys.iter_mut().zip(words.chunks(4)) {
+            for (bytes, word) in round_key.chunks_mut(4).zip(words) {
+                bytes.copy_from_slice(word)
+            }
+        }
+        Aes256 { round_keys, sbox }
+    }
+
+    fn encrypt(&self, block: &[u8; 16]) -> [u8; 16] {
+        let mut state = *block;
+        add_round_key(&mut state, &self.round_keys[0]);
+        for round in 1..=ROUNDS {
+            // SubBytes and ShiftRows, the state is column by column
+            let mut shifted = [0u8; 16];
+            for col in 0..4 {
+                for row in 0..4 {
+                    shifted[col * 4 + row] = self.sbox[state[(col + row) % 4 * 4 + row] as usize]
+                }
+            }
+            state = shifted;
+            if round != ROUNDS {
+                for col in state.chunks_mut(4) {
+                    let (a0, a1, a2, a3) = (col[0], col[1], col[2], col[3]);
+                    let all = a0 ^ a1 ^ a2 ^ a3;
+                    col[0] ^= all ^ xtime(a0 ^ a1);
+                    col[1] ^= all ^ xtime(a1 ^ a2);
+                    col[2] ^= all ^ xtime(a2 ^ a3);
+                    col[3] ^= all ^ xtime(a3 ^ a0);
+                }
+            }
+            add_round_key(&mut state, &self.round_keys[round])
+        }
+        state
+    }
+}
+
+fn add_round_key(state: &mut [u8; 16], key: &[u8; 16]) {
+    for (s, k) in state.iter_mut().zip(key) {
+        *s ^= k
+    }
+}
+
+/// multiplication by x in GF(2^8) by x^8 + x^4 + x^3 + x + 1
+fn xtime(b: u8) -> u8 {
+    b << 1 ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+/// The substitution box is the multiplicative inverse followed by the affine transformation,
+/// the inverses are found walking powers of the generator 3 and of its inverse together.
+fn sbox() -> [u8; 256] {
+    let mut res = [0u8; 256];
+    let (mut p, mut q) = (1u8, 1u8);
+    loop {
+        // p *= 3
+        p ^= xtime(p);
+        // q /= 3
+        q ^= q << 1;
+        q ^= q << 2;
+        q ^= q << 4;
+        if q & 0x80 != 0 {
+            q ^= 0x09
+        }
+        res[p as usize] = 0x63 ^ q ^ q.rotate_left(1) ^ q.rotate_left(2) ^ q.rotate_left(3) ^ q.rotate_left(4);
+        if p == 1 {
+            break
+        }
+    }
+    res[0] = 0x63;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// test cases 13, 14 and 16 of the GCM specification
+    #[test]
+    fn vectors() {
+        assert_eq!(open(&[0; KEY_LEN], &[0; NONCE_LEN], &[], &bytes("530f8afbc74536b9a963b4f1c4cb738b")), Some(Vec::new()));
+        let sealed = bytes("cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919");
+        assert_eq!(open(&[0; KEY_LEN], &[0; NONCE_LEN], &[], &sealed), Some(vec![0; 16]));
+        let key = bytes("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308").try_into().unwrap();
+        let nonce = bytes("cafebabefacedbaddecaf888").try_into().unwrap();
+        let aad = bytes("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+        let mut sealed = bytes("522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f66276fc6ece0f4e1768cddf8853bb2d551b");
+        assert_eq!(open(&key, &nonce, &aad, &sealed), Some(bytes("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39")));
+        assert_eq!(open(&key, &nonce, &aad[1..], &sealed), None);
+        sealed[0] ^= 1;
+        assert_eq!(open(&key, &nonce, &aad, &sealed), None);
+        assert_eq!(open(&key, &nonce, &aad, &sealed[..TAG_LEN - 1]), None);
+    }
+}
@@ -0,0 +1,81 @@
+/*
+andOTP plain JSON backup
+
+    [
+        {
+            "secret": BASE32,
+            "issuer": "provider",
+            "label": "account",         // older backups have "provider:account" labels without the issuer
+            "digits": 6,
+            "type": "TOTP",             // TOTP, HOTP, STEAM, MOTP
+            "algorithm": "SHA1",
+            "period": 30,
+            "counter": 0,               // HOTP only
+            "tags": ["tag name"]
+        }
+    ]
+
+An encrypted backup isn't JSON and has to be exported again as a plain one.
+*/
+use crate::{TOTPError, account::Account, import::{self, Entry, Importer, Object, text, num, array, objects}};
+use simjson::JsonData;
+
+pub struct AndOtp;
+
+impl Importer for AndOtp {
+    fn format(&self) -> &'static str {
+        "andOTP"
+    }
+
+    fn recognize(&self, json: &JsonData) -> bool {
+        matches!(json, JsonData::Arr(entries) if !entries.is_empty() && entries.iter().all(|entry|
+            matches!(entry, JsonData::Data(entry) if entry.contains_key("secret") && entry.contains_key("type"))))
+    }
+
+    fn entries(&self, json: &JsonData, _password: &str) -> Result<Vec<Entry>, TOTPError> {
+        match json {
+            JsonData::Arr(entries) => Ok(objects(entries).map(parse_entry).collect()),
+            _ => Err(TOTPError::new("not an andOTP backup")),
+        }
+    }
+}
+
+fn parse_entry(entry: &Object) -> Entry {
+    let groups = array(entry, "tags").iter().filter_map(|tag| match tag {
+        JsonData::Text(tag) if !tag.trim().is_empty() => Some(tag.trim().to_string()),
+        _ => None,
+    }).collect();
+    let mut issuer = text(entry, "issuer").map(str::to_string);
+    let mut name = text(entry, "label").unwrap_or_default().to_string();
+    if issuer.is_none() && let Some((prefix, label)) = name.split_once(':') {
+        issuer = Some(prefix.trim().to_string()).filter(|prefix| !prefix.is_empty());
+        name = label.trim().to_string()
+    }
+    let account = import::account(text(entry, "type").unwrap_or("totp"), text(entry, "secret").unwrap_or_default(),
+        text(entry, "algorithm"), num(entry, "digits"), num(entry, "period"), num(entry, "counter"))
+        .map(|account| Account { issuer: issuer.clone(), ..account });
+    Entry { groups, issuer, name, account }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Algorithm;
+
+    const BACKUP: &str = r#"[{"secret":"JBSWY3DPEHPK3PXP","issuer":"GitHub","label":"john","digits":8,"type":"TOTP","algorithm":"SHA256","period":60,"tags":["Work"," Home ",""]},{"secret":"GEZDGNBVGY3TQOJQ","label":"Bank:jane","digits":6,"type":"HOTP","algorithm":"SHA1","counter":3,"tags":[]},{"secret":"ABCD","label":"pin","type":"MOTP","tags":[]}]"#;
+
+    #[test]
+    fn backup() {
+        let json = simjson::parse(BACKUP);
+        assert!(AndOtp.recognize(&json));
+        let entries = AndOtp.entries(&json, "").unwrap();
+        let [john, jane, pin] = &entries[..] else { panic!("3 entries expected") };
+        assert_eq!(john.groups, ["Work", "Home"]);
+        let account = john.account.as_ref().unwrap();
+        assert_eq!((account.algorithm, account.digits, account.period, account.issuer.as_deref()), (Algorithm::Sha256, 8, 60, Some("GitHub")));
+        // the issuer of an older backup is the label prefix
+        assert_eq!((jane.issuer.as_deref(), jane.name.as_str(), jane.account.as_ref().unwrap().counter), (Some("Bank"), "jane", Some(3)));
+        assert_eq!(pin.account.as_ref().err().map(String::as_str), Some("MOTP OTP type isn't supported"));
+        assert!(!AndOtp.recognize(&simjson::parse("[]")));
+    }
+}
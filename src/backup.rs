@@ -0,0 +1,139 @@
+/*
+Rotating backups of the accounts database
+
+    .simtotp/directory.db                       // the database
+    .simtotp/directory-20261018T120000Z.db      // a copy of the database before a write at the UTC time
+    .simtotp/backups                            // the number of kept copies, 5 when absent, 0 turns backups off
+
+Copies stay encrypted by the password which was used when they were made. The oldest copies beyond
+the number get removed on every write. A legacy XORed DB isn't copied, since the password can be recovered
+from it, and writes of HOTP counters and used steps don't make copies, otherwise a few logins would push
+out all of them.
+*/
+use crate::dbcrypt;
+use std::{fs, io, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+pub const DEFAULT_COUNT: usize = 5;
+
+const CONFIG: &str = "backups";
+const PREFIX: &str = "directory-";
+const EXTENSION: &str = ".db";
+/// YYYYMMDDTHHMMSSZ
+const TIMESTAMP_LEN: usize = 16;
+
+/// The number of kept backups, configured in the directory of the database.
+pub fn count(db: &Path) -> usize {
+    db.parent().and_then(|dir| fs::read_to_string(dir.join(CONFIG)).ok())
+        .and_then(|count| count.trim().parse().ok()).unwrap_or(DEFAULT_COUNT)
+}
+
+/// Copies the current database to a timestamped backup and removes the oldest backups beyond the count.
+/// Nothing is done when the database doesn't exist yet or it's a legacy one. A backup of the same second
+/// isn't replaced, because it has an older state.
+pub fn rotate(db: &Path, count: usize) -> io::Result<()> {
+    if count == 0 || !db.exists() {
+        return Ok(())
+    }
+    let data = fs::read(db)?;
+    if dbcrypt::is_legacy(&data) {
+        return Ok(())
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let backup = db.with_file_name(format!("{PREFIX}{}{EXTENSION}", timestamp(now)));
+    if !backup.exists() {
+        fs::write(backup, data)?;
+    }
+    for name in list(db)?.into_iter().skip(count) {
+        fs::remove_file(db.with_file_name(name))?
+    }
+    Ok(())
+}
+
+/// Names of backup files from the newest one.
+pub fn list(db: &Path) -> io::Result<Vec<String>> {
+    let Some(dir) = db.parent() else {
+        return Ok(Vec::new())
+    };
+    let mut res: Vec<_> = fs::read_dir(dir)?.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| created(name).is_some()).collect();
+    // timestamps are ordered as text
+    res.sort_by(|a, b| b.cmp(a));
+    Ok(res)
+}
+
+/// The path of the backup, `None` when the name isn't a name of an existing backup.
+pub fn path(db: &Path, name: &str) -> Option<PathBuf> {
+    created(name)?;
+    Some(db.with_file_name(name)).filter(|path| path.is_file())
+}
+
+/// The creation time of the backup as YYYY-MM-DDTHH:MM:SSZ, `None` when it isn't a backup name.
+pub fn created(name: &str) -> Option<String> {
+    let stamp = name.strip_prefix(PREFIX)?.strip_suffix(EXTENSION)?;
+    let b = stamp.as_bytes();
+    let valid = stamp.len() == TIMESTAMP_LEN && b[8] == b'T' && b[15] == b'Z' &&
+        b[..8].iter().chain(&b[9..15]).all(u8::is_ascii_digit);
+    valid.then(|| format!("{}-{}-{}T{}:{}:{}Z", &stamp[..4], &stamp[4..6], &stamp[6..8], &stamp[9..11], &stamp[11..13], &stamp[13..15]))
+}
+
+/// Formats Unix time as YYYYMMDDTHHMMSSZ.
+fn timestamp(unix_time: u64) -> String {
+    let (days, secs) = (unix_time / 86400, unix_time % 86400);
+    // civil date of days since 1970-01-01, eras of 400 years start on March 1
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        for (unix_time, stamp) in [(0, "19700101T000000Z"), (951825600, "20000229T120000Z"), (1760745599, "20251017T235959Z"),
+            (4107542400, "21000301T000000Z")] {
+            assert_eq!(timestamp(unix_time), stamp);
+        }
+        assert_eq!(created("directory-20261018T120000Z.db").as_deref(), Some("2026-10-18T12:00:00Z"));
+        for name in ["directory.db", "directory-20261018T120000Z.json", "directory-2026101XT120000Z.db", "directory-20261018120000Z.db",
+            "directory-20261018T1200Z.db"] {
+            assert_eq!(created(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = std::env::temp_dir().join(format!("simtotp-backup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("directory.db");
+        fs::write(&db, b"SIMTOTP\0state").unwrap();
+        for stamp in ["20200101T000000Z", "20210101T000000Z", "20220101T000000Z"] {
+            fs::write(dir.join(format!("directory-{stamp}.db")), b"SIMTOTP\0old").unwrap();
+        }
+        assert_eq!(count(&db), DEFAULT_COUNT);
+        fs::write(dir.join("backups"), "2\n").unwrap();
+        rotate(&db, count(&db)).unwrap();
+        let names = list(&db).unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[1], "directory-20220101T000000Z.db");
+        assert_eq!(fs::read(path(&db, &names[0]).unwrap()).unwrap(), b"SIMTOTP\0state");
+        assert_eq!(path(&db, "directory-20200101T000000Z.db"), None);
+        assert_eq!(path(&db, "backups"), None);
+        rotate(&db, 0).unwrap();
+        assert_eq!(list(&db).unwrap(), names);
+        // a legacy DB isn't copied
+        fs::write(&db, b"XORed").unwrap();
+        fs::remove_file(path(&db, &names[0]).unwrap()).unwrap();
+        rotate(&db, 2).unwrap();
+        assert_eq!(list(&db).unwrap(), &names[1..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
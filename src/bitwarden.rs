@@ -0,0 +1,109 @@
+/*
+Bitwarden unencrypted JSON export
+
+    {
+        "encrypted": false,
+        "folders": [{"id": ID, "name": "folder name"}],
+        "collections": [{"id": ID, "name": "collection name"}],     // an organization export
+        "items": [
+            {
+                "type": 1,              // a login
+                "name": "item name",
+                "folderId": ID,
+                "collectionIds": [ID],
+                "login": {"username": "user", "totp": TOTP}
+            }
+        ]
+    }
+
+    TOTP is one of
+        otpauth://totp/...              // Key URI
+        steam://BASE32                  // Steam Guard secret
+        BASE32                          // a secret with default parameters
+
+Items without TOTP aren't accounts and get ignored.
+*/
+use crate::{TOTPError, otpauth, account::{Account, Encoding}, import::{self, Entry, Importer, Object, text, object, array, objects}};
+use simjson::JsonData;
+use std::collections::HashMap;
+
+pub struct Bitwarden;
+
+impl Importer for Bitwarden {
+    fn format(&self) -> &'static str {
+        "Bitwarden"
+    }
+
+    fn recognize(&self, json: &JsonData) -> bool {
+        matches!(json, JsonData::Data(export) if export.contains_key("items") && export.contains_key("encrypted"))
+    }
+
+    fn entries(&self, json: &JsonData, _password: &str) -> Result<Vec<Entry>, TOTPError> {
+        let JsonData::Data(export) = json else {
+            return Err(TOTPError::new("not a Bitwarden export"))
+        };
+        if matches!(export.get("encrypted"), Some(JsonData::Bool(true))) {
+            return Err(TOTPError::new("an encrypted Bitwarden export isn't supported, export it as unencrypted JSON"))
+        }
+        let names = |list| objects(array(export, list))
+            .filter_map(|group| Some((text(group, "id")?, text(group, "name")?))).collect::<HashMap<_, _>>();
+        let (folders, collections) = (names("folders"), names("collections"));
+        Ok(objects(array(export, "items")).filter_map(|item| {
+            let login = object(item, "login")?;
+            let totp = text(login, "totp")?;
+            let mut groups: Vec<String> = text(item, "folderId").and_then(|id| folders.get(id))
+                .map(|name| name.to_string()).into_iter().collect();
+            groups.extend(array(item, "collectionIds").iter().filter_map(|id| match id {
+                JsonData::Text(id) => collections.get(id.as_str()).map(|name| name.to_string()),
+                _ => None,
+            }));
+            Some(parse_item(item, login, totp, groups))
+        }).collect())
+    }
+}
+
+fn parse_item(item: &Object, login: &Object, totp: &str, groups: Vec<String>) -> Entry {
+    let item_name = text(item, "name").map(str::to_string);
+    let username = text(login, "username").map(str::to_string);
+    if otpauth::is_otpauth(totp) {
+        return match otpauth::parse(totp) {
+            Ok(uri) => {
+                let issuer = uri.issuer.or(item_name);
+                let name = username.or(Some(uri.name).filter(|name| !name.is_empty())).unwrap_or_default();
+                let account = Account { issuer: issuer.clone(), ..uri.account };
+                Entry { groups, issuer, name, account: Ok(account) }
+            }
+            Err(err) => Entry { groups, issuer: item_name.clone(), name: username.or(item_name).unwrap_or_default(),
+                account: Err(format!("the otpauth URI is malformed: {}", err.cause)) },
+        }
+    }
+    let (secret, encoding) = match import::steam_secret(totp) {
+        Some(secret) => (secret, Encoding::Steam),
+        None => (totp, Encoding::Decimal),
+    };
+    let account = import::account("totp", secret, None, None, None, None)
+        .map(|account| Account { issuer: item_name.clone(), encoding, ..account });
+    Entry { groups, issuer: item_name.clone(), name: username.or(item_name).unwrap_or_default(), account }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"{"encrypted":false,"folders":[{"id":"f1","name":"Work"}],"collections":[{"id":"c1","name":"Shared"}],"items":[{"type":1,"name":"GitHub","folderId":"f1","collectionIds":["c1"],"login":{"username":"john","totp":"otpauth://totp/GitHub:john?secret=JBSWY3DPEHPK3PXP&digits=8"}},{"type":1,"name":"Steam","folderId":null,"login":{"username":"gamer","totp":"steam://MFRGGZDFMZTWQ2LK"}},{"type":1,"name":"Bank","login":{"totp":"gezd gnbv gy3t qojq"}},{"type":1,"name":"Mail","login":{"username":"jane","totp":"otpauth://totp/Mail:jane"}},{"type":1,"name":"Forum","login":{"username":"mary"}},{"type":2,"name":"Note"}]}"#;
+
+    #[test]
+    fn export() {
+        let entries = Bitwarden.entries(&simjson::parse(EXPORT), "").unwrap();
+        let [github, steam, bank, mail] = &entries[..] else { panic!("4 entries expected") };
+        assert_eq!((github.groups.as_slice(), github.issuer.as_deref(), github.name.as_str()),
+            (&["Work".to_string(), "Shared".to_string()][..], Some("GitHub"), "john"));
+        assert_eq!(github.account.as_ref().unwrap().digits, 8);
+        assert_eq!(steam.account.as_ref().map(|account| (account.secret.as_str(), account.encoding)), Ok(("MFRGGZDFMZTWQ2LK", Encoding::Steam)));
+        assert_eq!((bank.name.as_str(), bank.account.as_ref().unwrap().secret.as_str()), ("Bank", "GEZDGNBVGY3TQOJQ"));
+        assert!(mail.account.as_ref().unwrap_err().starts_with("the otpauth URI is malformed: "));
+        let encrypted = EXPORT.replace(r#""encrypted":false"#, r#""encrypted":true"#);
+        assert_eq!(Bitwarden.entries(&simjson::parse(&encrypted), "").err().unwrap().cause,
+            "an encrypted Bitwarden export isn't supported, export it as unencrypted JSON");
+    }
+}
@@ -0,0 +1,229 @@
+/*
+ChaCha20-Poly1305 AEAD as described in RFC 8439
+
+    chacha20_aead_encrypt(aad, key, iv, constant, plaintext):
+        nonce = constant | iv
+        otk = poly1305_key_gen(key, nonce)
+        ciphertext = chacha20_encrypt(key, 1, nonce, plaintext)
+        mac_data = aad | pad16(aad)
+        mac_data |= ciphertext | pad16(ciphertext)
+        mac_data |= num_to_8_le_bytes(aad.length)
+        mac_data |= num_to_8_le_bytes(ciphertext.length)
+        tag = poly1305_mac(mac_data, otk)
+        return (ciphertext, tag)
+*/
+use crate::constant_time_eq;
+use std::convert::TryInto;
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+// "expand 32-byte k"
+const SIGMA: [u32; 4] = [0x61707865, 0x3320646e, 0x79622d32, 0x6b206574];
+
+/// Encrypts the plain text and appends the authentication tag to the result.
+pub fn seal(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], plain: &[u8]) -> Vec<u8> {
+    let mut res = plain.to_vec();
+    chacha20_xor(key, 1, nonce, &mut res);
+    let tag = compute_tag(key, nonce, aad, &res);
+    res.extend_from_slice(&tag);
+    res
+}
+
+/// Verifies the authentication tag and decrypts the data, `None` is returned
+/// when the data were tampered or the key is wrong.
+pub fn open(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < TAG_LEN {
+        return None
+    }
+    let (cipher, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    let expected = compute_tag(key, nonce, aad, cipher);
+    if !constant_time_eq(&expected, tag) {
+        return None
+    }
+    let mut res = cipher.to_vec();
+    chacha20_xor(key, 1, nonce, &mut res);
+    Some(res)
+}
+
+fn compute_tag(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], cipher: &[u8]) -> [u8; TAG_LEN] {
+    let otk = chacha20_block(key, 0, nonce);
+    let mut poly = Poly1305::new(otk[0..32].try_into().unwrap());
+    poly.update_padded(aad);
+    poly.update_padded(cipher);
+    let mut lens = [0u8; 16];
+    lens[0..8].copy_from_slice(&(aad.len() as u64).to_le_bytes());
+    lens[8..16].copy_from_slice(&(cipher.len() as u64).to_le_bytes());
+    poly.update_padded(&lens);
+    poly.finalize()
+}
+
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]); s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]); s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[0..4].copy_from_slice(&SIGMA);
+    for (i, word) in key.chunks(4).enumerate() {
+        state[4 + i] = u32::from_le_bytes(word.try_into().unwrap());
+    }
+    state[12] = counter;
+    for (i, word) in nonce.chunks(4).enumerate() {
+        state[13 + i] = u32::from_le_bytes(word.try_into().unwrap());
+    }
+    let mut working = state;
+    for _ in 0..10 {
+        // column rounds
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        // diagonal rounds
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+    let mut res = [0u8; 64];
+    for i in 0..16 {
+        res[i * 4..i * 4 + 4].copy_from_slice(&working[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    res
+}
+
+fn chacha20_xor(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let stream = chacha20_block(key, counter.wrapping_add(i as u32), nonce);
+        for (b, k) in chunk.iter_mut().zip(stream.iter()) {
+            *b ^= k
+        }
+    }
+}
+
+/// Poly1305 one-time authenticator using 26 bits limbs.
+struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8; 32]) -> Self {
+        let le = |i: usize| u32::from_le_bytes(key[i..i + 4].try_into().unwrap());
+        Poly1305 {
+            r: [
+                le(0) & 0x3ffffff,
+                (le(3) >> 2) & 0x3ffff03,
+                (le(6) >> 4) & 0x3ffc0ff,
+                (le(9) >> 6) & 0x3f03fff,
+                (le(12) >> 8) & 0x00fffff,
+            ],
+            h: [0; 5],
+            pad: [le(16), le(20), le(24), le(28)],
+        }
+    }
+
+    /// Processes the data padded with zeros to a multiple of 16 bytes.
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.block(&block)
+        }
+    }
+
+    fn block(&mut self, m: &[u8; 16]) {
+        let le = |i: usize| u32::from_le_bytes(m[i..i + 4].try_into().unwrap());
+        let [r0, r1, r2, r3, r4] = self.r.map(|v| v as u64);
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+        let h0 = (self.h[0] + (le(0) & 0x3ffffff)) as u64;
+        let h1 = (self.h[1] + ((le(3) >> 2) & 0x3ffffff)) as u64;
+        let h2 = (self.h[2] + ((le(6) >> 4) & 0x3ffffff)) as u64;
+        let h3 = (self.h[3] + ((le(9) >> 6) & 0x3ffffff)) as u64;
+        let h4 = (self.h[4] + ((le(12) >> 8) | (1 << 24))) as u64;
+
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+
+        // partial carry propagation
+        d1 += d0 >> 26;
+        d2 += d1 >> 26;
+        d3 += d2 >> 26;
+        d4 += d3 >> 26;
+        let h0 = (d0 & 0x3ffffff) + (d4 >> 26) * 5;
+        let h1 = (d1 & 0x3ffffff) + (h0 >> 26);
+        self.h = [(h0 & 0x3ffffff) as u32, h1 as u32, (d2 & 0x3ffffff) as u32, (d3 & 0x3ffffff) as u32,
+            (d4 & 0x3ffffff) as u32];
+    }
+
+    fn finalize(self) -> [u8; 16] {
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+        // full carry
+        h2 += h1 >> 26; h1 &= 0x3ffffff;
+        h3 += h2 >> 26; h2 &= 0x3ffffff;
+        h4 += h3 >> 26; h3 &= 0x3ffffff;
+        h0 += (h4 >> 26) * 5; h4 &= 0x3ffffff;
+        h1 += h0 >> 26; h0 &= 0x3ffffff;
+
+        // compute h - p and select it when h >= p
+        let mut g0 = h0 + 5;
+        let mut g1 = h1 + (g0 >> 26); g0 &= 0x3ffffff;
+        let mut g2 = h2 + (g1 >> 26); g1 &= 0x3ffffff;
+        let mut g3 = h3 + (g2 >> 26); g2 &= 0x3ffffff;
+        let mut g4 = (h4 + (g3 >> 26)).wrapping_sub(1 << 26); g3 &= 0x3ffffff;
+        let mask = (g4 >> 31).wrapping_sub(1);
+        g0 &= mask; g1 &= mask; g2 &= mask; g3 &= mask; g4 &= mask;
+        let mask = !mask;
+        h0 = (h0 & mask) | g0;
+        h1 = (h1 & mask) | g1;
+        h2 = (h2 & mask) | g2;
+        h3 = (h3 & mask) | g3;
+        h4 = (h4 & mask) | g4;
+
+        // h mod 2^128 + pad
+        let words = [
+            h0 | (h1 << 26),
+            (h1 >> 6) | (h2 << 20),
+            (h2 >> 12) | (h3 << 14),
+            (h3 >> 18) | (h4 << 8),
+        ];
+        let mut res = [0u8; 16];
+        let mut carry = 0u64;
+        for i in 0..4 {
+            let f = words[i] as u64 + self.pad[i] as u64 + carry;
+            res[i * 4..i * 4 + 4].copy_from_slice(&(f as u32).to_le_bytes());
+            carry = f >> 32;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 8439 AEAD test vector
+    #[test]
+    fn aead() {
+        let key: [u8; KEY_LEN] = std::array::from_fn(|i| 0x80 + i as u8);
+        let nonce = [0x07, 0x00, 0x00, 0x00, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47];
+        let aad = [0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7];
+        let plain = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let sealed = seal(&key, &nonce, &aad, plain);
+        assert_eq!(simweb::to_hex(&sealed), "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b61161ae10b594f09e26a7e902ecbd0600691");
+        assert_eq!(open(&key, &nonce, &aad, &sealed).as_deref(), Some(&plain[..]));
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert_eq!(open(&key, &nonce, &aad, &tampered), None);
+        assert_eq!(open(&key, &nonce, &aad[1..], &sealed), None);
+        assert_eq!(open(&key, &nonce, &aad, &sealed[..TAG_LEN - 1]), None);
+    }
+}
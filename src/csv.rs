@@ -0,0 +1,252 @@
+/*
+CSV as described in RFC 4180
+
+    a record per line, fields are separated by commas, the first record is a header, lines end by LF or CRLF
+    a field with commas, quotes or line breaks is enclosed in quotes, a quote in it is doubled
+
+Accounts are exported with the header
+
+    namespace,account,issuer,secret,digits,period,algorithm,type,counter,encoding,t0
+
+where type is totp or hotp, the counter is given only for HOTP, and the encoding is decimal or steam.
+
+An import maps columns by header names, so exports of password managers are read as they are:
+
+    Apple Passwords:    Title,URL,Username,Password,Notes,OTPAuth
+    KeePassXC:          Group,Title,Username,Password,URL,Notes,TOTP,...
+    Bitwarden:          folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp
+
+A secret column can have an otpauth URI, then the rest of the values come from the URI, or a steam:// secret
+of Steam Guard codes. Rows without a secret, like passwords without OTP, get ignored. A type other than totp, hotp
+or steam is a type of a password manager item, like login of Bitwarden, and it's ignored too.
+*/
+use crate::{TOTPError, Namespaces, otpauth, account::{Account, Encoding}, import::{self, Entry}};
+
+pub const HEADER: [&str; 11] = ["namespace", "account", "issuer", "secret", "digits", "period", "algorithm", "type", "counter",
+    "encoding", "t0"];
+
+/// header names of a field, the case is ignored
+const ALIASES: [(&str, &[&str]); 11] = [
+    ("namespace", &["namespace", "group", "folder"]),
+    ("account", &["account", "username", "login_username", "user name", "login"]),
+    ("issuer", &["issuer", "title", "name"]),
+    ("secret", &["secret", "otpauth", "totp", "login_totp", "otp"]),
+    ("digits", &["digits"]),
+    ("period", &["period"]),
+    ("algorithm", &["algorithm"]),
+    ("type", &["type", "otp type"]),
+    ("counter", &["counter"]),
+    ("encoding", &["encoding"]),
+    ("t0", &["t0"]),
+];
+
+const OTP_TYPES: [&str; 3] = ["totp", "hotp", "steam"];
+
+/// Makes CSV of all accounts ordered by namespaces and accounts.
+pub fn export(namespaces: &Namespaces) -> String {
+    let mut res = record(&HEADER);
+    let mut names: Vec<_> = namespaces.keys().collect();
+    names.sort();
+    for name in names {
+        let mut accounts: Vec<_> = namespaces[name].iter().collect();
+        accounts.sort_by_key(|(acn, _)| *acn);
+        for (acn, account) in accounts {
+            let (kind, counter) = match account.counter {
+                Some(counter) => ("hotp", counter.to_string()),
+                None => ("totp", String::new()),
+            };
+            res.push_str(&record(&[name, acn, account.issuer.as_deref().unwrap_or_default(), &account.secret,
+                &account.digits.to_string(), &account.period.to_string(), account.algorithm.name(), kind, &counter,
+                account.encoding.name(), &account.t0.to_string()]))
+        }
+    }
+    res
+}
+
+fn record(fields: &[&str]) -> String {
+    let mut res = fields.iter().map(|field| if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }).collect::<Vec<_>>().join(",");
+    res.push('\n');
+    res
+}
+
+/// Reads entries of CSV, `mapping` is a list of `field:header` pairs separated by commas, it maps
+/// columns which header names aren't known.
+pub fn entries(text: &str, mapping: &str) -> Result<Vec<Entry>, TOTPError> {
+    let mut records = parse(text.strip_prefix('\u{feff}').unwrap_or(text))?.into_iter();
+    let header = records.next().ok_or_else(|| TOTPError::new("no CSV header"))?;
+    let find = |names: &[&str]| header.iter().position(|column| names.iter().any(|name| column.trim().eq_ignore_ascii_case(name)));
+    let mut columns: Vec<(&str, Option<usize>)> = ALIASES.iter().map(|(field, names)| (*field, find(names))).collect();
+    for pair in mapping.split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (field, column) = pair.split_once(':').ok_or_else(|| TOTPError::new(format!("the column mapping '{pair}' isn't field:header")))?;
+        let slot = columns.iter_mut().find(|(name, _)| name.eq_ignore_ascii_case(field.trim()))
+            .ok_or_else(|| TOTPError::new(format!("unknown field '{}', it can be one of {}", field.trim(), HEADER.join(", "))))?;
+        slot.1 = Some(find(&[column.trim()]).ok_or_else(|| TOTPError::new(format!("no column '{}' in the CSV header", column.trim())))?)
+    }
+    let column = |field| columns.iter().find(|(name, _)| *name == field).and_then(|(_, column)| *column);
+    let secret_column = column("secret").ok_or_else(|| TOTPError::new("no secret column, specify it by the column mapping"))?;
+
+    Ok(records.filter(|record| record.get(secret_column).is_some_and(|secret| !secret.trim().is_empty())).map(|record| {
+        let value = |field| column(field).and_then(|i| record.get(i)).map(|value| value.trim()).filter(|value| !value.is_empty());
+        // a group path keeps only the last group
+        let groups = value("namespace").and_then(|group| group.rsplit('/').next()).map(|group| vec![group.to_string()])
+            .unwrap_or_default();
+        let secret = value("secret").unwrap_or_default();
+        if otpauth::is_otpauth(secret) {
+            return match otpauth::parse(secret) {
+                Ok(uri) => {
+                    let issuer = uri.issuer.or(value("issuer").map(str::to_string));
+                    let name = value("account").map(str::to_string).or(Some(uri.name).filter(|name| !name.is_empty()))
+                        .unwrap_or_default();
+                    Entry { groups, issuer: issuer.clone(), name, account: Ok(Account { issuer, ..uri.account }) }
+                }
+                Err(err) => Entry { groups, issuer: value("issuer").map(str::to_string),
+                    name: value("account").unwrap_or_default().to_string(),
+                    account: Err(format!("the otpauth URI is malformed: {}", err.cause)) },
+            }
+        }
+        let issuer = value("issuer").map(str::to_string);
+        let account = row_account(value, secret).map(|account| Account { issuer: issuer.clone(), ..account });
+        Entry { groups, issuer, name: value("account").unwrap_or_default().to_string(), account }
+    }).collect())
+}
+
+/// Makes an account of a row without an otpauth URI, absent values get defaults.
+fn row_account<'a>(value: impl Fn(&'static str) -> Option<&'a str>, secret: &str) -> Result<Account, String> {
+    let (digits, period) = (number::<f64>(value("digits"), "digits")?, number::<f64>(value("period"), "period")?);
+    // the counter is read as a whole, since a float loses large values
+    let counter = number::<u64>(value("counter"), "counter")?;
+    let t0 = number::<u64>(value("t0"), "t0")?.unwrap_or_default();
+    let encoding = value("encoding").map(|name| Encoding::parse(name).ok_or_else(|| format!("unsupported encoding '{name}'")))
+        .transpose()?;
+    let (secret, kind) = match import::steam_secret(secret) {
+        Some(secret) => (secret, "steam"),
+        None => (secret, value("type").filter(|kind| OTP_TYPES.iter().any(|otp| kind.eq_ignore_ascii_case(otp))).unwrap_or("totp")),
+    };
+    let account = import::account(kind, secret, value("algorithm"), digits, period, None)?;
+    Ok(Account { counter: account.counter.map(|_| counter.unwrap_or_default()), encoding: encoding.unwrap_or(account.encoding),
+        t0, ..account })
+}
+
+fn number<T: std::str::FromStr>(value: Option<&str>, field: &str) -> Result<Option<T>, String> {
+    value.map(|value| value.parse().map_err(|_| format!("invalid {field} '{value}'"))).transpose()
+}
+
+/// Splits the text to records of fields.
+fn parse(text: &str) -> Result<Vec<Vec<String>>, TOTPError> {
+    let mut res = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                // a quoted field
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"')
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1
+                            }
+                            field.push(c)
+                        }
+                        None => return Err(TOTPError::new(format!("unclosed quote in CSV record at line {line}"))),
+                    }
+                }
+                if !matches!(chars.peek(), None | Some(',' | '\r' | '\n')) {
+                    return Err(TOTPError::new(format!("a quoted CSV field is followed by text at line {line}")))
+                }
+            }
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                res.push(std::mem::take(&mut record));
+                line += 1
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        res.push(record)
+    }
+    // blank lines aren't records
+    res.retain(|record| record.len() > 1 || record.first().is_some_and(|field| !field.is_empty()));
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::Algorithm;
+    use std::collections::HashMap;
+
+    #[test]
+    fn export_round_trip() {
+        let account = Account { issuer: Some("Acme, \"Inc\"".to_string()), digits: 8, period: 60, algorithm: Algorithm::Sha256,
+            ..Account::new("JBSWY3DPEHPK3PXP".to_string()) };
+        let namespaces: Namespaces = HashMap::from([("Work".to_string(), HashMap::from([("john\ndoe".to_string(), account)]))]);
+        let csv = export(&namespaces);
+        assert_eq!(csv, concat!("namespace,account,issuer,secret,digits,period,algorithm,type,counter,encoding,t0\n",
+            "Work,\"john\ndoe\",\"Acme, \"\"Inc\"\"\",JBSWY3DPEHPK3PXP,8,60,SHA256,totp,,decimal,0\n"));
+        let read = entries(&csv.replace('\n', "\r\n"), "").unwrap();
+        let [entry] = &read[..] else { panic!("1 entry expected") };
+        assert_eq!((entry.groups.as_slice(), entry.name.as_str(), entry.issuer.as_deref()), (&["Work".to_string()][..], "john\r\ndoe", Some("Acme, \"Inc\"")));
+        let account = entry.account.as_ref().unwrap();
+        assert_eq!((account.digits, account.period, account.algorithm), (8, 60, Algorithm::Sha256));
+    }
+
+    #[test]
+    fn code_types() {
+        let hotp = Account { counter: Some(u64::MAX - 1), ..Account::new("JBSWY3DPEHPK3PXP".to_string()) };
+        let steam = Account { encoding: Encoding::Steam, t0: 100, ..Account::new("MFRGGZDFMZTWQ2LK".to_string()) };
+        let namespaces: Namespaces = HashMap::from([("Work".to_string(), HashMap::from([("hotp".to_string(), hotp), ("steam".to_string(), steam)]))]);
+        let read = entries(&export(&namespaces), "").unwrap();
+        let [hotp, steam] = &read[..] else { panic!("2 entries expected") };
+        assert_eq!(hotp.account.as_ref().unwrap().counter, Some(u64::MAX - 1));
+        let steam = steam.account.as_ref().unwrap();
+        assert_eq!((steam.counter, steam.encoding, steam.t0), (None, Encoding::Steam, 100));
+        let invalid = "secret,encoding\nJBSWY3DP,base64\n";
+        assert_eq!(entries(invalid, "").unwrap()[0].account.as_ref().unwrap_err(), "unsupported encoding 'base64'");
+    }
+
+    #[test]
+    fn header_aliases() {
+        let bitwarden = "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+            Work/Dev,0,login,GitHub,,,0,https://github.com,john,pw,otpauth://totp/GitHub:john?secret=JBSWY3DPEHPK3PXP&digits=8\n\
+            ,0,login,Mail,,,0,,jane,pw,\n\
+            ,0,login,Bank,,,0,,mary,pw,gezd gnbv\n\
+            ,0,login,Steam,,,0,,gamer,pw,steam://MFRGGZDFMZTWQ2LK\n";
+        let read = entries(bitwarden, "").unwrap();
+        let [github, bank, steam] = &read[..] else { panic!("3 entries expected") };
+        assert_eq!((github.groups.as_slice(), github.issuer.as_deref(), github.name.as_str()), (&["Dev".to_string()][..], Some("GitHub"), "john"));
+        assert_eq!(github.account.as_ref().unwrap().digits, 8);
+        assert_eq!((bank.issuer.as_deref(), bank.account.as_ref().unwrap().secret.as_str()), (Some("Bank"), "GEZDGNBV"));
+        assert_eq!(steam.account.as_ref().map(|account| (account.secret.as_str(), account.encoding)), Ok(("MFRGGZDFMZTWQ2LK", Encoding::Steam)));
+        let custom = "\u{feff}Site,Login,Key,Size\nGitHub,john,JBSWY3DPEHPK3PXP,x\n";
+        assert_eq!(entries(custom, "").err().unwrap().cause, "no secret column, specify it by the column mapping");
+        let mapped = entries(custom, "secret:key, account:login").unwrap();
+        assert_eq!((mapped[0].name.as_str(), mapped[0].account.is_ok()), ("john", true));
+        assert_eq!(entries(custom, "secret:key,digits:size").unwrap()[0].account.as_ref().unwrap_err(), "invalid digits 'x'");
+    }
+
+    #[test]
+    fn malformed() {
+        for (csv, mapping, cause) in [("", "", "no CSV header"), ("secret\n\"JBSWY3DP", "", "unclosed quote in CSV record at line 2"),
+            ("secret\n\"JBSW\"Y3DP\n", "", "a quoted CSV field is followed by text at line 2"),
+            ("secret\n", "secret", "the column mapping 'secret' isn't field:header"),
+            ("secret\n", "code:secret", "unknown field 'code', it can be one of namespace, account, issuer, secret, digits, period, algorithm, type, counter, encoding, t0"),
+            ("secret\n", "secret:key", "no column 'key' in the CSV header")] {
+            assert_eq!(entries(csv, mapping).err().unwrap().cause, cause);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+/*
+Password based encryption of the accounts database
+
+    header:
+        magic           8 bytes   // "SIMTOTP\0"
+        version         1 byte    // 1
+        created         8 bytes   // Unix time of the DB creation, big-endian
+        kdf id          1 byte    // 1 - PBKDF2-HMAC-SHA1
+        iterations      4 bytes   // big-endian, from 10000 to 10000000
+        salt length     1 byte
+        salt            salt length bytes
+        cipher id       1 byte    // 1 - ChaCha20-Poly1305
+        nonce           12 bytes
+    body:
+        ciphertext | tag          // the header is authenticated as AAD
+
+A file without the magic is a legacy DB, which is JSON XORed with the repeated password bytes.
+*/
+use crate::{TOTPError, chacha20poly1305::{self, KEY_LEN, NONCE_LEN}, pbkdf2::pbkdf2_hmac_sha1, random};
+use std::{convert::TryInto, time::{SystemTime, UNIX_EPOCH}};
+
+const MAGIC: &[u8; 8] = b"SIMTOTP\0";
+const VERSION: u8 = 1;
+
+const KDF_PBKDF2_HMAC_SHA1: u8 = 1;
+const CIPHER_CHACHA20_POLY1305: u8 = 1;
+
+const ITERATIONS: u32 = 100_000;
+/// iterations accepted from a file, an uploaded DB can't keep the process busy for hours
+const ITERATIONS_RANGE: std::ops::RangeInclusive<u32> = 10_000..=10_000_000;
+const SALT_LEN: usize = 16;
+
+/// Container properties which aren't a part of the encrypted data.
+pub struct Container {
+    /// Unix time when the DB was created
+    pub created: u64,
+    /// the data were read from a headerless XOR file and have to be written back in the current format
+    pub legacy: bool,
+}
+
+impl Container {
+    /// Properties of a DB which doesn't exist yet.
+    pub fn new() -> Self {
+        Container {
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+            legacy: false,
+        }
+    }
+}
+
+/// Checks that the data are a legacy XORed DB, which reveals the password.
+pub fn is_legacy(data: &[u8]) -> bool {
+    !data.starts_with(MAGIC)
+}
+
+/// Encrypts the data with a key derived from the password using a fresh salt and nonce.
+pub fn encrypt(password: &str, data: &[u8], container: &Container) -> Result<Vec<u8>, TOTPError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    random::fill(&mut salt).and_then(|_| random::fill(&mut nonce))
+        .map_err(|e| TOTPError::new(format!("no random data: {e}")))?;
+    let mut res = MAGIC.to_vec();
+    res.push(VERSION);
+    res.extend_from_slice(&container.created.to_be_bytes());
+    res.push(KDF_PBKDF2_HMAC_SHA1);
+    res.extend_from_slice(&ITERATIONS.to_be_bytes());
+    res.push(SALT_LEN as u8);
+    res.extend_from_slice(&salt);
+    res.push(CIPHER_CHACHA20_POLY1305);
+    res.extend_from_slice(&nonce);
+    let key = derive_key(password, &salt, ITERATIONS);
+    let sealed = chacha20poly1305::seal(&key, &nonce, &res, data);
+    res.extend_from_slice(&sealed);
+    Ok(res)
+}
+
+/// Decrypts the data produced by `encrypt` or a legacy XORed DB.
+/// An authentication failure is reported as a wrong password.
+pub fn decrypt(password: &str, data: &[u8]) -> Result<(Vec<u8>, Container), TOTPError> {
+    if is_legacy(data) {
+        return Ok((decrypt_legacy(password, data), Container{ legacy: true, ..Container::new() }))
+    }
+    let truncated = || TOTPError::new("truncated DB header");
+    let mut pos = MAGIC.len();
+    let version = *data.get(pos).ok_or_else(truncated)?;
+    if version != VERSION {
+        return Err(TOTPError::new(format!("unsupported DB version {version}")))
+    }
+    let created = u64::from_be_bytes(data.get(pos + 1..pos + 9).ok_or_else(truncated)?.try_into().unwrap());
+    pos += 9;
+    let kdf = *data.get(pos).ok_or_else(truncated)?;
+    if kdf != KDF_PBKDF2_HMAC_SHA1 {
+        return Err(TOTPError::new(format!("unsupported key derivation {kdf}")))
+    }
+    let iterations = u32::from_be_bytes(data.get(pos + 1..pos + 5).ok_or_else(truncated)?.try_into().unwrap());
+    if !ITERATIONS_RANGE.contains(&iterations) {
+        return Err(TOTPError::new(format!("unsupported number of key derivation iterations {iterations}")))
+    }
+    let salt_len = *data.get(pos + 5).ok_or_else(truncated)? as usize;
+    pos += 6;
+    let salt = data.get(pos..pos + salt_len).ok_or_else(truncated)?;
+    pos += salt_len;
+    let cipher = *data.get(pos).ok_or_else(truncated)?;
+    if cipher != CIPHER_CHACHA20_POLY1305 {
+        return Err(TOTPError::new(format!("unsupported cipher {cipher}")))
+    }
+    let header_len = pos + 1 + NONCE_LEN;
+    let nonce: [u8; NONCE_LEN] = data.get(pos + 1..header_len).ok_or_else(truncated)?.try_into().unwrap();
+    let key = derive_key(password, salt, iterations);
+    let plain = chacha20poly1305::open(&key, &nonce, &data[..header_len], &data[header_len..])
+        .ok_or_else(|| TOTPError::new("wrong password"))?;
+    Ok((plain, Container{ created, legacy: false }))
+}
+
+fn decrypt_legacy(password: &str, data: &[u8]) -> Vec<u8> {
+    let password = password.as_bytes();
+    let mut res = data.to_vec();
+    if !password.is_empty() {
+        for (i, b) in res.iter_mut().enumerate() {
+            *b ^= password[i % password.len()]
+        }
+    }
+    res
+}
+
+fn derive_key(password: &str, salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2_hmac_sha1(password.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data = br#"{"":{}}"#;
+        let container = Container { created: 1760000000, legacy: false };
+        let encrypted = encrypt("secret", data, &container).unwrap();
+        let (decrypted, read) = decrypt("secret", &encrypted).unwrap();
+        assert_eq!(decrypted, data);
+        assert_eq!((read.created, read.legacy), (1760000000, false));
+        assert_eq!(decrypt("Secret", &encrypted).err().unwrap().cause, "wrong password");
+    }
+
+    #[test]
+    fn tampered() {
+        let encrypted = encrypt("secret", b"data", &Container::new()).unwrap();
+        // the creation time and a salt byte of the header, the last byte of the tag
+        for pos in [16, 23, encrypted.len() - 1] {
+            let mut tampered = encrypted.clone();
+            tampered[pos] ^= 1;
+            assert!(decrypt("secret", &tampered).is_err());
+        }
+        for len in [9, 20, 40] {
+            assert_eq!(decrypt("secret", &encrypted[..len]).err().unwrap().cause, "truncated DB header");
+        }
+        assert!(decrypt("secret", &encrypted[..encrypted.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn legacy_migration() {
+        let json = br#"{"":{}}"#;
+        let legacy: Vec<_> = json.iter().zip(b"pw".iter().cycle()).map(|(b, p)| b ^ p).collect();
+        let (decrypted, container) = decrypt("pw", &legacy).unwrap();
+        assert_eq!(decrypted, json);
+        assert!(container.legacy);
+        let (decrypted, container) = decrypt("pw", &encrypt("pw", &decrypted, &container).unwrap()).unwrap();
+        assert_eq!(decrypted, json);
+        assert!(!container.legacy);
+    }
+
+    #[test]
+    fn iterations_range() {
+        let encrypted = encrypt("secret", b"data", &Container::new()).unwrap();
+        for iterations in [0, 9_999, u32::MAX] {
+            let mut changed = encrypted.clone();
+            changed[18..22].copy_from_slice(&iterations.to_be_bytes());
+            assert_eq!(decrypt("secret", &changed).err().unwrap().cause, format!("unsupported number of key derivation iterations {iterations}"));
+        }
+    }
+}
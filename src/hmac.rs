@@ -30,40 +30,101 @@ function computeBlockSizedKey is
 
     return  key
 */
-use crate::Sha1;
-// TODO use one instance Sha1
-pub fn hmac(key: &[u8], message: &[u8], block_size: usize) -> [u8; 20] {
-    let mut sha1 = Sha1::new();
-    let key = compute_block_sized_key(key, &mut Sha1::new(), block_size);
-
-    // Generate inner and outer keys
-    let mut inner_key = vec![0u8; block_size];
-    let mut outer_key = vec![0u8; block_size];
-    for i in 0..block_size {
-        inner_key[i] = 0x36 ^ key[i];
-        outer_key[i] = 0x5c ^ key[i];
+use crate::{Sha1, Sha256, Sha512};
+
+/// A hash function usable for HMAC.
+pub trait Digest: Clone {
+    /// the block size of the hash function in bytes
+    const BLOCK_SIZE: usize;
+    /// the hash length in bytes
+    const OUTPUT_LEN: usize;
+    type Output: AsRef<[u8]>;
+
+    fn new() -> Self;
+    fn update(&mut self, data: &[u8]);
+    /// Produces the hash and resets the hasher.
+    fn finalize(&mut self) -> Self::Output;
+
+    /// Computes the hash of the data at once.
+    fn digest(data: &[u8]) -> Self::Output {
+        let mut hasher = Self::new();
+        hasher.update(data);
+        hasher.finalize()
     }
+}
+
+impl Digest for Sha1 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_LEN: usize = 20;
+    type Output = [u8; 20];
+
+    fn new() -> Self { Sha1::new() }
+    fn update(&mut self, data: &[u8]) { Sha1::update(self, data) }
+    fn finalize(&mut self) -> Self::Output { Sha1::finalize(self) }
+}
+
+impl Digest for Sha256 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_LEN: usize = 32;
+    type Output = [u8; 32];
+
+    fn new() -> Self { Sha256::new() }
+    fn update(&mut self, data: &[u8]) { Sha256::update(self, data) }
+    fn finalize(&mut self) -> Self::Output { Sha256::finalize(self) }
+}
 
-    // Append the inner_key
-    let mut msg = vec![0u8; message.len() + block_size];
-    msg[0..inner_key.len()].copy_from_slice(&inner_key[..]);
-    msg[inner_key.len()..].copy_from_slice(message);
+impl Digest for Sha512 {
+    const BLOCK_SIZE: usize = 128;
+    const OUTPUT_LEN: usize = 64;
+    type Output = [u8; 64];
 
-    // Has the previous message and append the outer_key
-    let mut result = vec![0u8; block_size + 20];
-    result[0..outer_key.len()].copy_from_slice(&outer_key[..]);
-    result[outer_key.len()..outer_key.len() + 20].copy_from_slice(&Sha1::new().hash(&msg)[..20]);
+    fn new() -> Self { Sha512::new() }
+    fn update(&mut self, data: &[u8]) { Sha512::update(self, data) }
+    fn finalize(&mut self) -> Self::Output { Sha512::finalize(self) }
+}
 
-    // Hash the previous message
-    sha1.hash(&result)
+/// HMAC keyed with a secret, the hasher states after the inner and outer padded keys
+/// are precomputed, so computing a MAC for every next message costs only the message hashing.
+#[derive(Clone)]
+pub struct Hmac<D: Digest> {
+    inner: D,
+    outer: D,
 }
 
-fn compute_block_sized_key(key: &[u8], sha1: &mut Sha1, block_size: usize) -> Vec<u8> {
-    let mut res = vec![0u8; block_size];
-    if key.len() > block_size {
-        res[0..20].copy_from_slice(&sha1.hash(key))
+impl<D: Digest> Hmac<D> {
+    pub fn new(key: &[u8]) -> Self {
+        let key = compute_block_sized_key::<D>(key);
+
+        // Generate inner and outer keys
+        let inner_key: Vec<u8> = key.iter().map(|k| k ^ 0x36).collect();
+        let outer_key: Vec<u8> = key.iter().map(|k| k ^ 0x5c).collect();
+
+        let mut inner = D::new();
+        inner.update(&inner_key);
+        let mut outer = D::new();
+        outer.update(&outer_key);
+        Hmac { inner, outer }
     }
-    if key.len() <= block_size {
+
+    /// Computes HMAC of the message.
+    pub fn mac(&self, message: &[u8]) -> D::Output {
+        // Hash the message prepended by the inner key
+        let mut inner = self.inner.clone();
+        inner.update(message);
+        let inner_hash = inner.finalize();
+
+        // Hash the previous hash prepended by the outer key
+        let mut outer = self.outer.clone();
+        outer.update(inner_hash.as_ref());
+        outer.finalize()
+    }
+}
+
+fn compute_block_sized_key<D: Digest>(key: &[u8]) -> Vec<u8> {
+    let mut res = vec![0u8; D::BLOCK_SIZE];
+    if key.len() > D::BLOCK_SIZE {
+        res[0..D::OUTPUT_LEN].copy_from_slice(D::digest(key).as_ref())
+    } else {
         res[0..key.len()].copy_from_slice(key)
     }
     res
@@ -0,0 +1,240 @@
+/*
+Import of accounts exported by other authenticators
+
+An importer recognizes its format by the JSON structure and reads entries of the export. Entries get mapped
+to namespaces the same way for all formats:
+
+    groups of the entry (groups, folders, tags)  ->  a namespace per group
+    no groups                                    ->  the requested namespace, or the issuer one
+    the entry name                               ->  the account
+
+A new format is added by an `Importer` implementation listed in `IMPORTERS`.
+*/
+use crate::{TOTPError, Namespaces, aegis::Aegis, twofas::TwoFas, andotp::AndOtp, bitwarden::Bitwarden,
+    account::{Account, Algorithm, Encoding, MIN_DIGITS, MAX_DIGITS}};
+use simjson::JsonData;
+use std::collections::HashMap;
+
+pub type Object = HashMap<String, JsonData>;
+
+const STEAM_PREFIX: &str = "steam://";
+
+/// An entry of an export, the account is an error telling why it can't be represented.
+pub struct Entry {
+    /// names of groups of the entry
+    pub groups: Vec<String>,
+    pub issuer: Option<String>,
+    pub name: String,
+    pub account: Result<Account, String>,
+}
+
+/// A format of an authenticator export.
+pub trait Importer {
+    /// the format name shown to a user
+    fn format(&self) -> &'static str;
+    /// Checks that the JSON has the structure of the format.
+    fn recognize(&self, json: &JsonData) -> bool;
+    /// Reads entries of the export, the password is needed only for an encrypted one.
+    fn entries(&self, json: &JsonData, password: &str) -> Result<Vec<Entry>, TOTPError>;
+}
+
+pub const IMPORTERS: [&dyn Importer; 4] = [&Aegis, &TwoFas, &AndOtp, &Bitwarden];
+
+/// Finds the importer of the export format.
+pub fn detect(json: &JsonData) -> Option<&'static dyn Importer> {
+    IMPORTERS.into_iter().find(|importer| importer.recognize(json))
+}
+
+/// Accounts of an export mapped to namespaces.
+pub struct Import {
+    pub namespaces: Namespaces,
+    /// (namespace, account) in the order of the export
+    pub added: Vec<(String, String)>,
+    /// (namespace, account, reason)
+    pub skipped: Vec<(String, String, String)>,
+}
+
+impl Import {
+    /// Maps the entries, an entry without groups goes to the default namespace, or to the issuer one.
+    /// `no_namespace` is the reason to skip an entry when there's neither. An account name taken by another
+    /// imported or current account gets the issuer prefix as `issuer:name`, an entry is skipped when that is
+    /// taken too, or when it's the same account, so no account gets overwritten.
+    pub fn new(entries: Vec<Entry>, default_namespace: Option<String>, no_namespace: &str, current: &Namespaces) -> Self {
+        let mut res = Import { namespaces: HashMap::new(), added: Vec::new(), skipped: Vec::new() };
+        for entry in entries {
+            let groups = if entry.groups.is_empty() {
+                vec![default_namespace.clone().or(entry.issuer.clone()).unwrap_or_default()]
+            } else {
+                entry.groups
+            };
+            for name in groups {
+                match &entry.account {
+                    Ok(_) if name.is_empty() => res.skipped.push((name, entry.name.clone(), no_namespace.to_string())),
+                    Ok(_) if entry.name.is_empty() => res.skipped.push((name, entry.name.clone(), "no account name".to_string())),
+                    Ok(account) => {
+                        let taken = |acn: &str| res.namespaces.get(&name).and_then(|ns| ns.get(acn))
+                            .or_else(|| current.get(&name).and_then(|ns| ns.get(acn)));
+                        let candidates = [Some(entry.name.clone()), entry.issuer.as_ref().map(|issuer| format!("{issuer}:{}", entry.name))];
+                        let mut skip = "the account name is taken by another account";
+                        let mut free = None;
+                        for acn in candidates.into_iter().flatten() {
+                            match taken(&acn) {
+                                None => { free = Some(acn); break }
+                                Some(other) if other.same_codes(account) => { skip = "the account is already present"; break }
+                                Some(_) => (),
+                            }
+                        }
+                        match free {
+                            Some(acn) => {
+                                res.namespaces.entry(name.clone()).or_default().insert(acn.clone(), account.clone());
+                                res.added.push((name, acn))
+                            }
+                            None => res.skipped.push((name, entry.name.clone(), skip.to_string())),
+                        }
+                    }
+                    Err(reason) => res.skipped.push((name, entry.name.clone(), reason.clone())),
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Makes an account of values common for export formats, the type and the algorithm names
+/// are case insensitive, absent values get defaults.
+pub fn account(kind: &str, secret: &str, algorithm: Option<&str>, digits: Option<f64>, period: Option<f64>,
+    counter: Option<f64>) -> Result<Account, String> {
+    let mut res = Account::default();
+    match kind.to_ascii_lowercase().as_str() {
+        "totp" => (),
+        "hotp" => res.counter = Some(counter.unwrap_or_default() as u64),
+        // Steam Guard codes are 5 symbols regardless of digits
+        "steam" => res.encoding = Encoding::Steam,
+        _ => return Err(format!("{kind} OTP type isn't supported")),
+    }
+    res.secret = secret.chars().filter(|c| !c.is_whitespace() && *c != '=').collect::<String>().to_uppercase();
+    if res.secret.is_empty() {
+        return Err("no secret".to_string())
+    }
+    if base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &res.secret).is_none() {
+        return Err("the secret isn't valid base32".to_string())
+    }
+    if let Some(algorithm) = algorithm {
+        res.algorithm = Algorithm::parse(algorithm).ok_or_else(|| format!("{algorithm} algorithm isn't supported"))?
+    }
+    if let Some(period) = period {
+        res.period = Some(period).filter(|value| *value >= 1.0 && value.fract() == 0.0).map(|value| value as u64)
+            .ok_or_else(|| format!("invalid period {period}"))?
+    }
+    if let Some(digits) = digits && res.encoding == Encoding::Decimal {
+        res.digits = Some(digits as u32).filter(|value| (MIN_DIGITS..=MAX_DIGITS).contains(value))
+            .ok_or_else(|| format!("{digits} digits aren't supported"))?
+    }
+    Ok(res)
+}
+
+/// The secret of a `steam://` value, password managers keep Steam Guard secrets so.
+pub fn steam_secret(value: &str) -> Option<&str> {
+    value.get(..STEAM_PREFIX.len()).filter(|prefix| prefix.eq_ignore_ascii_case(STEAM_PREFIX)).map(|_| &value[STEAM_PREFIX.len()..])
+}
+
+/// A text of a non empty trimmed value.
+pub fn text<'a>(obj: &'a Object, name: &str) -> Option<&'a str> {
+    match obj.get(name) {
+        Some(JsonData::Text(text)) => Some(text.trim()).filter(|text| !text.is_empty()),
+        _ => None,
+    }
+}
+
+/// A number, some exports have numbers as text.
+pub fn num(obj: &Object, name: &str) -> Option<f64> {
+    match obj.get(name) {
+        Some(JsonData::Num(num)) => Some(*num),
+        Some(JsonData::Text(text)) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn object<'a>(obj: &'a Object, name: &str) -> Option<&'a Object> {
+    match obj.get(name) {
+        Some(JsonData::Data(obj)) => Some(obj),
+        _ => None,
+    }
+}
+
+pub fn array<'a>(obj: &'a Object, name: &str) -> &'a [JsonData] {
+    match obj.get(name) {
+        Some(JsonData::Arr(arr)) => arr,
+        _ => &[],
+    }
+}
+
+/// Objects of the array, other values are ignored.
+pub fn objects(arr: &[JsonData]) -> impl Iterator<Item = &Object> {
+    arr.iter().filter_map(|value| match value {
+        JsonData::Data(obj) => Some(obj),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(groups: &[&str], issuer: Option<&str>, name: &str, account: Result<Account, String>) -> Entry {
+        Entry { groups: groups.iter().map(|group| group.to_string()).collect(), issuer: issuer.map(str::to_string),
+            name: name.to_string(), account }
+    }
+
+    #[test]
+    fn detect_formats() {
+        for (json, format) in [(r#"{"version":1,"header":{"slots":null},"db":{"entries":[]}}"#, "Aegis"),
+            (r#"{"schemaVersion":4,"services":[]}"#, "2FAS"),
+            (r#"[{"secret":"JBSWY3DPEHPK3PXP","type":"TOTP"}]"#, "andOTP"),
+            (r#"{"encrypted":false,"items":[]}"#, "Bitwarden")] {
+            assert_eq!(detect(&simjson::parse(json)).map(|importer| importer.format()), Some(format));
+        }
+        assert!(detect(&simjson::parse(r#"{"accounts":[]}"#)).is_none());
+    }
+
+    #[test]
+    fn namespaces() {
+        let totp = Account::new("JBSWY3DPEHPK3PXP".to_string());
+        let entries = vec![entry(&["Work", "Home"], None, "john", Ok(totp.clone())),
+            entry(&[], Some("GitHub"), "jane", Ok(totp.clone())),
+            entry(&[], None, "mary", Ok(totp.clone())),
+            entry(&["Work"], None, "", Ok(totp.clone())),
+            entry(&["Work"], None, "pin", Err("motp OTP type isn't supported".to_string()))];
+        let import = Import::new(entries, None, "no namespace", &Namespaces::new());
+        assert_eq!(import.added, [("Work".to_string(), "john".to_string()), ("Home".to_string(), "john".to_string()),
+            ("GitHub".to_string(), "jane".to_string())]);
+        assert_eq!(import.skipped.iter().map(|(ns, name, reason)| format!("{ns}/{name}: {reason}")).collect::<Vec<_>>(),
+            ["/mary: no namespace", "Work/: no account name", "Work/pin: motp OTP type isn't supported"]);
+        assert_eq!(import.namespaces.len(), 3);
+        let import = Import::new(vec![entry(&[], Some("GitHub"), "jane", Ok(totp))], Some("Mine".to_string()), "", &Namespaces::new());
+        assert!(import.namespaces["Mine"].contains_key("jane"));
+    }
+
+    #[test]
+    fn account_values() {
+        let hotp = account("HOTP", "jbsw y3dp ehpk 3pxp==", Some("sha512"), Some(8.0), None, Some(4.0)).unwrap();
+        assert_eq!((hotp.secret.as_str(), hotp.algorithm, hotp.digits, hotp.counter), ("JBSWY3DPEHPK3PXP", Algorithm::Sha512, 8, Some(4)));
+        for (secret, algorithm, digits, period, cause) in [("", None, None, None, "no secret"), ("JBSW1", None, None, None, "the secret isn't valid base32"),
+            ("JBSWY3DP", Some("MD5"), None, None, "MD5 algorithm isn't supported"), ("JBSWY3DP", None, Some(4.0), None, "4 digits aren't supported"),
+            ("JBSWY3DP", None, None, Some(0.5), "invalid period 0.5")] {
+            assert_eq!(account("totp", secret, algorithm, digits, period, None).unwrap_err(), cause);
+        }
+    }
+
+    #[test]
+    fn name_collisions() {
+        let entry = |issuer: &str, secret: &str| entry(&["Work"], Some(issuer), "john", Ok(Account::new(secret.to_string())));
+        let mut current = Namespaces::new();
+        current.entry("Work".to_string()).or_default().insert("john".to_string(), Account::new("MFRGGZDFMZTWQ2LK".to_string()));
+        let import = Import::new(vec![entry("GitHub", "JBSWY3DPEHPK3PXP"), entry("GitLab", "GEZDGNBVGY3TQOJQ"),
+            entry("GitLab", "KRUGKIDROVUWG2ZA"), entry("Gitea", "MFRGGZDFMZTWQ2LK")], None, "", &current);
+        assert_eq!(import.added, [("Work".to_string(), "GitHub:john".to_string()), ("Work".to_string(), "GitLab:john".to_string())]);
+        let reasons: Vec<_> = import.skipped.iter().map(|(_, _, reason)| reason.as_str()).collect();
+        assert_eq!(reasons, ["the account name is taken by another account", "the account is already present"]);
+    }
+}
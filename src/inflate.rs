@@ -0,0 +1,295 @@
+/*
+DEFLATE decompression as described in RFC 1951, and the zlib wrapper of RFC 1950
+
+    zlib:   CMF | FLG | deflate blocks | Adler-32 of the data, big-endian
+    block:  BFINAL 1 bit | BTYPE 2 bits   // 0 - stored, 1 - fixed Huffman codes, 2 - dynamic Huffman codes
+    bits are read starting from the least significant bit of a byte, Huffman codes
+    starting from the most significant bit of a code
+*/
+use crate::TOTPError;
+
+const MAX_BITS: usize = 15;
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// order of code lengths of the code lengths alphabet
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses zlib data, the result can't exceed the limit.
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, TOTPError> {
+    if data.len() < 6 {
+        return Err(TOTPError::new("truncated zlib data"))
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err(TOTPError::new("not deflate compressed zlib data"))
+    }
+    if flg & 0x20 != 0 {
+        return Err(TOTPError::new("zlib preset dictionary isn't supported"))
+    }
+    let res = inflate(&data[2..], limit)?;
+    let expected = data.get(data.len() - 4..).map(|adler| u32::from_be_bytes(adler.try_into().unwrap()));
+    if expected != Some(adler32(&res)) {
+        return Err(TOTPError::new("zlib data checksum mismatch"))
+    }
+    Ok(res)
+}
+
+/// Decompresses raw deflate blocks.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, TOTPError> {
+    let mut bits = BitReader { data, pos: 0, bit: 0 };
+    let mut res = Vec::new();
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let len = bits.read_u16()?;
+                if len != !bits.read_u16()? {
+                    return Err(TOTPError::new("stored block length mismatch"))
+                }
+                let block = data.get(bits.pos..bits.pos + len as usize).ok_or_else(|| TOTPError::new("truncated stored block"))?;
+                res.extend_from_slice(block);
+                bits.pos += len as usize;
+            }
+            1 => {
+                let (lit_len, dist) = fixed_codes();
+                inflate_block(&mut bits, &lit_len, &dist, &mut res, limit)?
+            }
+            2 => {
+                let (lit_len, dist) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &lit_len, &dist, &mut res, limit)?
+            }
+            _ => return Err(TOTPError::new("invalid deflate block type")),
+        }
+        if res.len() > limit {
+            return Err(TOTPError::new("decompressed data are too large"))
+        }
+        if last {
+            return Ok(res)
+        }
+    }
+}
+
+fn inflate_block(bits: &mut BitReader, lit_len: &Huffman, dist: &Huffman, res: &mut Vec<u8>, limit: usize) -> Result<(), TOTPError> {
+    loop {
+        let symbol = bits.decode(lit_len)?;
+        if symbol < END_OF_BLOCK {
+            res.push(symbol as u8)
+        } else if symbol == END_OF_BLOCK {
+            return Ok(())
+        } else {
+            let i = (symbol - 257) as usize;
+            if i >= LENGTH_BASE.len() {
+                return Err(TOTPError::new("invalid length code"))
+            }
+            let len = LENGTH_BASE[i] as usize + bits.read(LENGTH_EXTRA[i])? as usize;
+            let i = bits.decode(dist)? as usize;
+            if i >= DIST_BASE.len() {
+                return Err(TOTPError::new("invalid distance code"))
+            }
+            let distance = DIST_BASE[i] as usize + bits.read(DIST_EXTRA[i])? as usize;
+            if distance > res.len() {
+                return Err(TOTPError::new("distance is too far back"))
+            }
+            // the copy can overlap the produced bytes
+            let start = res.len() - distance;
+            for k in 0..len {
+                res.push(res[start + k])
+            }
+        }
+        if res.len() > limit {
+            return Err(TOTPError::new("decompressed data are too large"))
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut BitReader) -> Result<(Huffman, Huffman), TOTPError> {
+    let hlit = bits.read(5)? as usize + 257;
+    let hdist = bits.read(5)? as usize + 1;
+    let hclen = bits.read(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER.iter().take(hclen) {
+        code_lengths[i] = bits.read(3)? as u8
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+    // literal/length and distance code lengths are one sequence
+    let mut lengths = Vec::with_capacity(hlit + hdist);
+    while lengths.len() < hlit + hdist {
+        let (value, repeat) = match bits.decode(&code_lengths)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| TOTPError::new("repeat of no code length"))?, 3 + bits.read(2)?),
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        if lengths.len() + repeat as usize > hlit + hdist {
+            return Err(TOTPError::new("too many code lengths"))
+        }
+        lengths.extend(std::iter::repeat_n(value, repeat as usize))
+    }
+    if lengths[END_OF_BLOCK as usize] == 0 {
+        return Err(TOTPError::new("no end of block code"))
+    }
+    Ok((Huffman::new(&lengths[..hlit]), Huffman::new(&lengths[hlit..])))
+}
+
+/// Canonical Huffman code as counts of codes of every length and symbols ordered by codes.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len]
+        }
+        let mut symbols = vec![0u16; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1
+            }
+        }
+        Huffman { counts, symbols }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u8) -> Result<u32, TOTPError> {
+        let mut res = 0;
+        for i in 0..bits {
+            let byte = *self.data.get(self.pos).ok_or_else(|| TOTPError::new("truncated deflate data"))?;
+            res |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1
+            }
+        }
+        Ok(res)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1
+        }
+    }
+
+    fn read_u16(&mut self) -> Result<u16, TOTPError> {
+        let bytes = self.data.get(self.pos..self.pos + 2).ok_or_else(|| TOTPError::new("truncated deflate data"))?;
+        self.pos += 2;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Reads a code bit by bit until it's in the range of codes of the current length.
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, TOTPError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= self.read(1)? as i32;
+            let count = huffman.counts[len] as i32;
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize])
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1
+        }
+        Err(TOTPError::new("invalid Huffman code"))
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a
+        }
+        a %= 65521;
+        b %= 65521
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    const URI: &[u8] = b"otpauth://totp/john?secret=JBSWY3DP&issuer=john";
+    /// the URI compressed by zlib in a stored block
+    const STORED: &str = "7801012f00d0ff6f7470617574683a2f2f746f74702f6a6f686e3f7365637265743d4a42535759334450266973737565723d6a6f686ea27c1148";
+    /// the URI compressed by zlib with fixed Huffman codes
+    const FIXED: &str = "7801cb2f29482c2dc9b0d2d72fc92f29d0cfcacfc8b32f4e4d2e4a2db1f5720a0e8f34760950cb2c2e2e4d2db205c90100a27c1148";
+    /// `base32_text` compressed by zlib with dynamic Huffman codes
+    const DYNAMIC: &str = "78da0dccc70180201000b09da8ef03912e4deafe8368060808e52bd2afec86f4c7ea5bfb3c216c7ba23c2d051f732777610f6b86edb794da91f0f36a6a4545f71c13715db0dd8e3427194684ab3820d12457b217c0ff7faa9b1c52";
+
+    fn base32_text() -> Vec<u8> {
+        (0..100).map(|i| b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567"[(i * i + i / 3) % 32]).collect()
+    }
+
+    #[test]
+    fn zlib_streams() {
+        assert_eq!(zlib_decompress(&bytes(STORED), 1000).unwrap(), URI);
+        assert_eq!(zlib_decompress(&bytes(FIXED), 1000).unwrap(), URI);
+        assert_eq!(zlib_decompress(&bytes(DYNAMIC), 1000).unwrap(), base32_text());
+    }
+
+    #[test]
+    fn malformed_streams() {
+        for stream in [STORED, FIXED, DYNAMIC] {
+            let data = bytes(stream);
+            for len in 0..data.len() {
+                assert!(zlib_decompress(&data[..len], 1000).is_err(), "truncated to {len}");
+            }
+            let mut corrupted = data.clone();
+            *corrupted.last_mut().unwrap() ^= 1;
+            assert_eq!(zlib_decompress(&corrupted, 1000).err().unwrap().cause, "zlib data checksum mismatch");
+            assert_eq!(zlib_decompress(&data, 40).err().unwrap().cause, "decompressed data are too large");
+            // any flipped bit of deflate blocks gives an error or other data
+            for pos in 2..data.len() - 4 {
+                for bit in 0..8 {
+                    corrupted.copy_from_slice(&data);
+                    corrupted[pos] ^= 1 << bit;
+                    let _ = inflate(&corrupted[2..], 1000);
+                }
+            }
+        }
+        assert_eq!(zlib_decompress(&[0x78, 0x02, 0, 0, 0, 0], 1000).err().unwrap().cause, "not deflate compressed zlib data");
+        assert_eq!(zlib_decompress(&[0x78, 0x20, 0, 0, 0, 0], 1000).err().unwrap().cause, "zlib preset dictionary isn't supported");
+        assert_eq!(inflate(&[0b111], 1000).err().unwrap().cause, "invalid deflate block type");
+        // a distance before the start of the data
+        assert_eq!(inflate(&[0x03, 0x02, 0x00], 1000).err().unwrap().cause, "distance is too far back");
+    }
+}
@@ -7,19 +7,60 @@ extern crate simweb;
 extern crate simjson;
 extern crate simcfg;
 mod sha1;
+mod sha256;
+mod sha512;
 mod hmac;
+mod pbkdf2;
+mod chacha20poly1305;
+mod random;
+mod dbcrypt;
+mod account;
+mod clock;
+mod otpauth;
+mod qrcode;
+mod qrdecode;
+mod inflate;
+mod png;
+mod migration;
+mod scrypt;
+mod aes256gcm;
+mod import;
+mod aegis;
+mod twofas;
+mod andotp;
+mod bitwarden;
+mod csv;
+mod pam;
+mod backup;
 use sha1::Sha1;
-use std::{time::{SystemTime, UNIX_EPOCH}, fmt, convert::TryInto, error::Error};
-use hmac::hmac;
+use sha256::Sha256;
+use sha512::Sha512;
+use std::{fmt, convert::TryInto, error::Error, ops::RangeInclusive};
+use hmac::Hmac;
+use dbcrypt::Container;
+use account::{Account, Algorithm, Encoding, MIN_DIGITS, MAX_DIGITS};
+use clock::{Clock, SystemClock, FixedClock};
+use import::{Import, Importer};
 use simweb::{WebPage,json_encode};
 use simjson::{JsonData::{self}};
    
 const VERSION: &str = env!("VERSION");
+/// default number of steps around the current one accepted by the code verification
+const DEFAULT_WINDOW: u64 = 1;
+const MAX_WINDOW: u64 = 10;
+/// Steam Guard codes are 5 symbols of the alphabet regardless of digits
+const STEAM_ALPHABET: &[u8; 26] = b"23456789BCDFGHJKMNPQRTVWXY";
+const STEAM_CODE_LEN: usize = 5;
 
 #[derive(Debug)]
 pub struct TOTPError {
     cause: String,
 }
+impl TOTPError {
+    pub fn new(cause: impl Into<String>) -> Self {
+        TOTPError { cause: cause.into() }
+    }
+}
 impl fmt::Display for TOTPError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Err: {}", self.cause)
@@ -32,38 +73,175 @@ impl std::error::Error for TOTPError {}
 /// # Arguments
 ///
 /// * `secret` - The secret key as a byte slice.
-/// * `digits` - The number of digits for the TOTP code (e.g., 6 or 8).
+/// * `digits` - The number of digits for the TOTP code (from 6 to 10).
 /// * `step_seconds` - The time step in seconds (e.g., 30).
+/// * `t0` - The Unix time to start counting time steps from (usually 0).
+/// * `algorithm` - The hash function of HMAC.
+/// * `encoding` - The way to present the code, decimal digits or the Steam alphabet.
+/// * `clock` - The source of the current time.
 ///
 /// # Returns
 ///
-/// An `Option<u32>` containing the TOTP code if successful, otherwise `None`.
-pub fn generate_totp(secret: &[u8], digits: u32, step_seconds: u64) -> Option<u32> {
-    let current_time_seconds = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .ok()?
-        .as_secs();
+/// An `Option` containing the TOTP code and the time step it's valid for if successful, otherwise `None`.
+pub fn generate_totp(secret: &[u8], digits: u32, step_seconds: u64, t0: u64, algorithm: Algorithm, encoding: Encoding,
+    clock: &dyn Clock) -> Option<(String, TimeStep)> {
+    generate_totp_at(secret, digits, step_seconds, t0, algorithm, encoding, clock.now()?)
+}
+
+/// Generates a TOTP code valid at the given Unix time, `None` is returned for a time before `t0`.
+pub fn generate_totp_at(secret: &[u8], digits: u32, step_seconds: u64, t0: u64, algorithm: Algorithm, encoding: Encoding,
+    unix_time: u64) -> Option<(String, TimeStep)> {
+    let time_step = time_step_at(step_seconds, t0, unix_time)?;
 
-    let time_step = current_time_seconds / step_seconds;
+    Some((generate_hotp(secret, digits, time_step.counter, algorithm, encoding), time_step))
+}
 
-    // Convert the time step to an 8-byte big-endian array.
-    let time_bytes = time_step.to_be_bytes();
+/// A time step as a moving factor of TOTP.
+pub struct TimeStep {
+    /// number of steps since T0
+    pub counter: u64,
+    /// Unix time of the step beginning
+    pub start: u64,
+    /// Unix time of the next step beginning
+    pub end: u64,
+    /// the time the step was calculated for
+    pub now: u64,
+}
 
-    let result = hmac(secret, &time_bytes, 64); 
-    
-    let code = hotp_from_hmac(&result, digits); 
+impl TimeStep {
+    /// Seconds until the step end.
+    pub fn remaining(&self) -> u64 {
+        self.end - self.now
+    }
+}
+
+/// Calculates the time step of the Unix time counted from `t0`, `None` is returned for a time before `t0`
+/// or a step ending beyond the Unix time range.
+fn time_step_at(step_seconds: u64, t0: u64, unix_time: u64) -> Option<TimeStep> {
+    let counter = unix_time.checked_sub(t0)? / step_seconds;
+    let start = t0 + counter * step_seconds;
+    // the last step of the Unix time has no end
+    let end = start.checked_add(step_seconds)?;
+    Some(TimeStep { counter, start, end, now: unix_time })
+}
+
+/// Generates an HOTP code for the counter value as described in RFC 4226.
+pub fn generate_hotp(secret: &[u8], digits: u32, counter: u64, algorithm: Algorithm, encoding: Encoding) -> String {
+    OtpKey::new(secret, algorithm).code(counter, digits, encoding)
+}
+
+/// HMAC keyed with an account secret, it generates codes for several moving factor values
+/// without repeating the key schedule.
+#[allow(clippy::large_enum_variant)]
+enum OtpKey {
+    Sha1(Hmac<Sha1>),
+    Sha256(Hmac<Sha256>),
+    Sha512(Hmac<Sha512>),
+}
 
-    Some(code)
+impl OtpKey {
+    fn new(secret: &[u8], algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha1 => OtpKey::Sha1(Hmac::new(secret)),
+            Algorithm::Sha256 => OtpKey::Sha256(Hmac::new(secret)),
+            Algorithm::Sha512 => OtpKey::Sha512(Hmac::new(secret)),
+        }
+    }
+
+    fn code(&self, counter: u64, digits: u32, encoding: Encoding) -> String {
+        // Convert the counter to an 8-byte big-endian array.
+        let counter_bytes = counter.to_be_bytes();
+
+        match self {
+            OtpKey::Sha1(hmac) => code_from_hmac(&hmac.mac(&counter_bytes), digits, encoding),
+            OtpKey::Sha256(hmac) => code_from_hmac(&hmac.mac(&counter_bytes), digits, encoding),
+            OtpKey::Sha512(hmac) => code_from_hmac(&hmac.mac(&counter_bytes), digits, encoding),
+        }
+    }
+}
+
+/// Verifies the code against codes of moving factor values shifted by offsets of the window.
+/// All offsets are checked regardless of a match, the first matched offset is returned.
+fn verify_code(key: &OtpKey, code: &str, digits: u32, encoding: Encoding, moving_factor: u64, window: RangeInclusive<i64>) -> Option<i64> {
+    let mut matched = None;
+    for offset in window {
+        let Some(factor) = moving_factor.checked_add_signed(offset) else { continue };
+        let expected = key.code(factor, digits, encoding);
+        if constant_time_eq(expected.as_bytes(), code.as_bytes()) && matched.is_none() {
+            matched = Some(offset)
+        }
+    }
+    matched
+}
+
+/// Verifies the code of the account within the window of steps around the time, HOTP accounts use
+/// the counter instead. An accepted code is stored as the last used one and resynchronizes the HOTP
+/// counter, the result is the JSON answer and whether the code got accepted.
+fn verify_account(account: &mut Account, secret: &[u8], code: &str, window: u64, now: Option<u64>) -> (String, bool) {
+    let key = OtpKey::new(secret, account.algorithm);
+    let window = window as i64;
+    let moving_factor = match account.counter {
+        Some(counter) => Some(counter),
+        None => now.and_then(|now| time_step_at(account.period, account.t0, now)).map(|step| step.counter)
+    };
+    let matched = moving_factor.and_then(|factor|
+        verify_code(&key, &code.to_ascii_uppercase(), account.digits, account.encoding, factor, -window..=window)
+            .map(|offset| (offset, factor.saturating_add_signed(offset))));
+    match matched {
+        // a code of the last accepted step or before can't be reused
+        Some((_, used)) if account.last_used.is_some_and(|last_used| used <= last_used) =>
+            (r#"{"valid":false,"error":"The code has been already used."}"#.to_string(), false),
+        // HOTP codes are accepted only ahead of the counter
+        Some((_, used)) if account.counter.is_some_and(|counter| used < counter) =>
+            (r#"{"valid":false}"#.to_string(), false),
+        Some((offset, used)) => {
+            account.last_used = Some(used);
+            if account.counter.is_some() {
+                // a match resynchronizes the counter, the maximum one stays used by last_used
+                account.counter = Some(used.saturating_add(1))
+            }
+            (format!(r#"{{"valid":true,"offset":{offset}}}"#), true)
+        }
+        None => (r#"{"valid":false}"#.to_string(), false)
+    }
+}
+
+/// Compares without an early exit on the first different byte.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
 }
 
 /// Extracts an HOTP code from an HMAC result.
 fn hotp_from_hmac(hmac_result: &[u8], digits: u32) -> u32 {
-    let offset = (hmac_result[19] & 0xf) as usize;
+    let otp = truncate(hmac_result);
+
+    // 10 digits modulo exceeds u32, the result fits u32 as it isn't greater than the 31-bit otp
+    let power_of_10 = 10u64.pow(digits);
+    (otp as u64 % power_of_10) as u32
+}
+
+/// Dynamic truncation of an HMAC result to a 31-bit value.
+fn truncate(hmac_result: &[u8]) -> u32 {
+    let offset = (hmac_result[hmac_result.len() - 1] & 0xf) as usize;
     let hmac_truncated = hmac_result[offset..offset + 4].to_vec();
-    let otp = u32::from_be_bytes(hmac_truncated.try_into().unwrap()) & 0x7fff_ffff;
+    u32::from_be_bytes(hmac_truncated.try_into().unwrap()) & 0x7fff_ffff
+}
 
-    let power_of_10 = 10u32.pow(digits);
-    otp % power_of_10
+/// Presents an HMAC result as a code in the encoding.
+fn code_from_hmac(hmac_result: &[u8], digits: u32, encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Decimal => format!("{:0>width$}", hotp_from_hmac(hmac_result, digits), width = digits as usize),
+        Encoding::Steam => {
+            // the truncated value gets written in the alphabet starting from the least significant position
+            let mut otp = truncate(hmac_result);
+            let mut res = String::with_capacity(STEAM_CODE_LEN);
+            for _ in 0..STEAM_CODE_LEN {
+                res.push(STEAM_ALPHABET[otp as usize % STEAM_ALPHABET.len()] as char);
+                otp /= STEAM_ALPHABET.len() as u32;
+            }
+            res
+        }
+    }
 }
 
 use base32::Alphabet;
@@ -71,16 +249,14 @@ use std::{env, fs::{self, read_to_string}, path::{PathBuf}, io::{self,Write}, co
     fmt::Write as fmtWrite,
 };
 
+/// accounts grouped by namespaces
+type Namespaces = HashMap<String, HashMap<String,Account>>;
+
 struct Response<'a> {
     json: &'a str,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-   #[cfg(test)]
-    {
-    let test = hmac(b"key", b"The quick brown fox jumps over the lazy dog", 64);
-    assert_eq!(simweb::to_hex(&test), "de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9")
-    }
     let totp = std::env::current_exe();
     
     let mut home = PathBuf::new();
@@ -120,7 +296,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         fs::create_dir_all(&home)?;
     }
     home.push("directory"); home.set_extension("db");
-    if std::env::var("QUERY_STRING").is_err() { // run as CLI
+    let cli = std::env::var("QUERY_STRING").is_err();
+    if cli { // run as CLI
         // CLI mode
         let args: Vec<String> = env::args().collect();
         if args.len() <= 3  {
@@ -128,9 +305,19 @@ fn main() -> Result<(), Box<dyn Error>> {
             eprintln!("No program arguments from web or CLI");
             std::process::exit(1)
         }
-        let query_str = format!("pass={}&op={}&name={}&account={}&secret={}",
+        // an otpauth URI is the third argument, optionally followed by a namespace
+        let mut query_str = if args[2] == "adur" || args[2] == "admg" {
+            format!("pass={}&op={}&uri={}&name={}", simweb::url_encode(&args[1]), args[2], simweb::url_encode(&args[3]),
+                if args.len() > 4 {simweb::url_encode(&args[4])} else {"".to_string()})
+        } else { format!("pass={}&op={}&name={}&account={}&secret={}",
             simweb::url_encode(&args[1]), args[2], if args.len() > 3 {simweb::url_encode(&args[3])} else {"".to_string()},
-            if args.len() > 4 {simweb::url_encode(&args[4])} else {"".to_string()}, if args.len() > 5 {args[5].clone()} else {"".to_string()});
+            if args.len() > 4 {simweb::url_encode(&args[4])} else {"".to_string()}, if args.len() > 5 {args[5].clone()} else {"".to_string()}) };
+        // the rest of arguments are optional parameters as name=value
+        for arg in args.iter().skip(6) {
+            if let Some((name, value)) = arg.split_once('=') {
+                write!(query_str, "&{name}={}", simweb::url_encode(value))?
+            }
+        }
         eprintln!("{query_str}");
         unsafe {
             env::set_var("QUERY_STRING",query_str)
@@ -156,8 +343,21 @@ fn main() -> Result<(), Box<dyn Error>> {
                 json:&format!(r#"{{"version":"v{VERSION}","ok":true}}"#),
             }.show())
     }
-    let mut namespaces = match read_db(&home, &password) {
-        Ok(namespaces) => namespaces,
+    // codes get generated for the current time, unless a moment is requested by `at`
+    let clock: Box<dyn Clock> = match web.param("at").filter(|at| !at.is_empty()) {
+        Some(at) => match at.parse::<u64>() {
+            Ok(at) => Box::new(FixedClock(at)),
+            Err(_) => {
+                Response {
+                    json:r#"{"error":"The at parameter has to be a Unix time in seconds."}"#,
+                }.show();
+                return Ok(())
+            }
+        }
+        None => Box::new(SystemClock)
+    };
+    let (mut namespaces, container) = match read_db(&home, &password) {
+        Ok(db) => db,
         Err(err) => {
             Response {
                 json:&format!(r#"{{"error":"DB can't be read correctly {err}"}}"#),
@@ -165,8 +365,38 @@ fn main() -> Result<(), Box<dyn Error>> {
             return Ok(())
         }
     };
+    // backup ops need the password of the DB too, a restore replaces the current data
+    match op {
+        "lsbk" => { // list of db backups
+            let json = match backup::list(&home) {
+                Ok(names) => format!("[{}]", names.iter().map(|name| format!(r#"{{"backup":"{}","created":"{}"}}"#,
+                    json_encode(name), backup::created(name).unwrap_or_default())).collect::<Vec<_>>().join(",")),
+                Err(err) => format!(r#"{{"error":"Backups can't be listed: {}"}}"#, json_encode(&err.to_string())),
+            };
+            return Ok(Response { json: &json }.show())
+        }
+        "rsbk" => { // restore a db backup, it stays encrypted by the password of the backup
+            let backup_password = web.param("uppassword").unwrap_or_default();
+            let restored = web.param("backup").and_then(|name| backup::path(&home, &name))
+                .ok_or_else(|| r#"{"error":"No such backup."}"#.to_string())
+                .and_then(|path| read_db(&path, &backup_password)
+                    .map_err(|err| format!(r#"{{"error":"The backup can't be read: {}"}}"#, json_encode(&err.cause))));
+            return match restored {
+                Ok((namespaces, container)) => {
+                    Response { json: r#"{"ok":true}"# }.show();
+                    // the current state becomes a backup too, so the restore can be undone
+                    store_db(&home, &backup_password, &namespaces, &container, true)
+                }
+                Err(json) => Ok(Response { json: &json }.show()),
+            }
+        }
+        _ => ()
+    }
     let mut json:&str = "{}";
-    let mut update_db = false;
+    // a legacy DB gets migrated to the current format
+    let mut update_db = container.legacy;
+    // a change of HOTP counters or used steps only isn't worth a backup
+    let mut counters_only = false;
     let res;
     let code_str: String;
     match op {
@@ -186,20 +416,41 @@ fn main() -> Result<(), Box<dyn Error>> {
                 _ => json = r#"{"error":"no namespace name"}"#,
             }
         }
-        "gen" => { // generate TOTP code
+        "gen" => { // generate TOTP or HOTP code
             if let Some(name) = web.param("name") &&
                 let Some(acn) = web.param("account") &&
-                let Some(ns) = namespaces.get(&name) &&
-                let Some(web_secret) = ns.get(&acn) {
-                    let digits = 6;
-                    let step = 30;
-                    if let Some(secret) = base32::decode(Alphabet::Rfc4648 { padding: false }, web_secret) {
-                        match generate_totp(&secret, digits, step) {
-                            Some(code) => {
-                                code_str = format!(r#"{{"code":"{:0>width$}"}}"#, code, width = digits as usize);
+                let Some(ns) = namespaces.get_mut(&name) &&
+                let Some(account) = ns.get_mut(&acn) {
+                    let digits = account.digits;
+                    if let Some(secret) = base32::decode(Alphabet::Rfc4648 { padding: false }, &account.secret) {
+                        let hotp_counter = account.counter;
+                        // the code and extra response fields
+                        let code = match hotp_counter {
+                            // the used counter value is persisted incremented, the maximum one can't be used
+                            Some(counter) => counter.checked_add(1).map(|next| {
+                                account.counter = Some(next);
+                                update_db = true;
+                                counters_only = true;
+                                (generate_hotp(&secret, digits, counter, account.algorithm, account.encoding), format!(r#","counter":{counter}"#))
+                            }),
+                            None => generate_totp(&secret, digits, account.period, account.t0, account.algorithm, account.encoding, clock.as_ref())
+                                .map(|(code, step)| {
+                                    let mut validity = format!(r#","remaining":{},"start":{},"end":{}"#,
+                                        step.remaining(), step.start, step.end);
+                                    if web.param("next").as_deref() == Some("true") {
+                                        write!(validity, r#","next":"{}""#,
+                                            generate_hotp(&secret, digits, step.counter + 1, account.algorithm, account.encoding)).unwrap();
+                                    }
+                                    (code, validity)
+                                })
+                        };
+                        match code {
+                            Some((code, extra)) => {
+                                code_str = format!(r#"{{"code":"{code}"{extra}}}"#);
                                 json = &code_str;
-                                eprintln!("Current TOTP code: {:0>width$}", code, width = digits as usize);
+                                eprintln!("Current TOTP code: {code}");
                             }
+                            None if hotp_counter.is_some() => json = r#"{"error":"The HOTP counter reached its maximum, reset it."}"#,
                             None => {
                                 json = r#"{"error":"Failed to generate TOTP code."}"#;
                                 eprintln!("Failed to generate TOTP code.");
@@ -212,33 +463,262 @@ fn main() -> Result<(), Box<dyn Error>> {
                 json = r#"{"error":"Insufficient info to generate TOTP code."}"#;
             }
         }
+        "vrfy" => { // verify a code
+            if let Some(name) = web.param("name") &&
+                let Some(acn) = web.param("account") &&
+                let Some(code) = web.param("code") &&
+                let Some(ns) = namespaces.get_mut(&name) &&
+                let Some(account) = ns.get_mut(&acn) {
+                let window = match web.param("window").filter(|window| !window.is_empty()) {
+                    Some(window) => window.parse::<u64>().ok().filter(|&window| window <= MAX_WINDOW),
+                    None => Some(DEFAULT_WINDOW)
+                };
+                match (base32::decode(Alphabet::Rfc4648 { padding: false }, &account.secret), window) {
+                    (None, _) => json = r#"{"error":"The secret isn't valid base32 value."}"#,
+                    (_, None) => json = r#"{"error":"The window has to be a number of steps up to 10."}"#,
+                    (Some(secret), Some(window)) => {
+                        // codes are always verified at the current time
+                        let (answer, accepted) = verify_account(account, &secret, &code, window, SystemClock.now());
+                        if accepted {
+                            update_db = true;
+                            counters_only = true;
+                        }
+                        code_str = answer;
+                        json = &code_str;
+                    }
+                }
+            } else {
+                json = r#"{"error":"Insufficient info to verify the code."}"#;
+            }
+        }
         "adac" => { // add an account with a secret
             if let Some(name) = web.param("name") &&
                 let Some(acn) = web.param("account") &&
                 let Some(secret) = web.param("secret") {
-                if let Some(ns) = namespaces.get_mut(&name) {
-                    ns.insert(acn, secret);
+                let mut account = Account::new(secret);
+                if let Err(err) = apply_params(&web, &mut account) {
+                    json = err;
                 } else {
-                    let mut ns = HashMap::new();
-                    ns.insert(acn, secret);
-                    namespaces.insert(name.clone(),ns);
+                    namespaces.entry(name).or_default().insert(acn, account);
+                    update_db = true;
+                    json = r#"{"ok":true}"#;
                 }
-                update_db = true;
-                json = r#"{"ok":true}"#;
             } else {
                  json = r#"{"error":"Insufficient info to add an account."}"#;
             }
         }
+        "adur" => { // add an account from otpauth URI
+            match web.param("uri").filter(|uri| !uri.is_empty()) {
+                None => json = r#"{"error":"no otpauth URI"}"#,
+                Some(uri) => {
+                    code_str = add_uri(&web, &mut namespaces, &uri).inspect(|_| update_db = true).unwrap_or_else(|err| err);
+                    json = &code_str
+                }
+            }
+        }
+        "admg" => { // import Google Authenticator accounts transfer
+            match web.param("uri").filter(|uri| !uri.is_empty()) {
+                None => json = r#"{"error":"no otpauth-migration URI"}"#,
+                Some(uri) => {
+                    code_str = import_migration(&web, &mut namespaces, &uri).inspect(|_| update_db = !dry_run(&web)).unwrap_or_else(|err| err);
+                    json = &code_str
+                }
+            }
+        }
+        "upqr" => { // read otpauth URI from QR code of an uploaded PNG image
+            match web.param("upFile") {
+                None => json = r#"{"error":"nothing was uploaded"}"#,
+                Some(file) => {
+                    let up_file = PathBuf::from(&file);
+                    let decoded = fs::read(&up_file).map_err(|err| TOTPError::new(err.to_string()))
+                        .and_then(|data| png::decode(&data))
+                        .and_then(|image| qrdecode::decode(&image));
+                    // a file of CLI isn't an upload
+                    if !cli {
+                        let _ = fs::remove_file(up_file);
+                    }
+                    code_str = match decoded {
+                        Ok(uri) if web.param("add").as_deref() == Some("true") => if migration::is_migration(&uri) {
+                                // a dry run only previews accounts of the migration
+                                import_migration(&web, &mut namespaces, &uri).inspect(|_| update_db = !dry_run(&web))
+                            } else {
+                                add_uri(&web, &mut namespaces, &uri).inspect(|_| update_db = true)
+                            }.unwrap_or_else(|err| err),
+                        Ok(uri) => format!(r#"{{"uri":"{}"}}"#, json_encode(&uri)),
+                        Err(err) => format!(r#"{{"error":"No QR code read: {}"}}"#, json_encode(&err.cause)),
+                    };
+                    json = &code_str
+                }
+            }
+        }
+        "upag" | "upim" => { // import an uploaded Aegis vault, or an export of a recognized format
+            match web.param("upFile") {
+                None => json = r#"{"error":"nothing was uploaded"}"#,
+                Some(file) => {
+                    let up_file = PathBuf::from(&file);
+                    let data = fs::read_to_string(&up_file);
+                    if !cli {
+                        let _ = fs::remove_file(up_file);
+                    }
+                    let importer: Option<&dyn Importer> = if op == "upag" { Some(&aegis::Aegis) } else { None };
+                    code_str = match data {
+                        Ok(data) => import_export(&web, &mut namespaces, &data, importer)
+                            .inspect(|_| update_db = !dry_run(&web)).unwrap_or_else(|err| err),
+                        Err(err) => format!(r#"{{"error":"The export can't be read: {}"}}"#, json_encode(&err.to_string())),
+                    };
+                    json = &code_str
+                }
+            }
+        }
+        "exur" => { // export accounts as otpauth URIs
+            if !password_confirmed(&web, &password) {
+                json = r#"{"error":"The password isn't confirmed."}"#;
+            } else if let Some(name) = web.param("name") &&
+                let Some(ns) = namespaces.get(&name) {
+                match web.param("account").filter(|acn| !acn.is_empty()) {
+                    Some(acn) => match ns.get(&acn) {
+                        Some(account) => {
+                            res = format!(r#"{{"uri":"{}"}}"#, json_encode(&otpauth::build(&name, &acn, account)));
+                            json = &res
+                        }
+                        None => json = r#"{"error":"No such account."}"#,
+                    }
+                    None => {
+                        res = format!("[{}]", ns.iter().map(|(acn, account)| format!(r#"{{"account":"{}","uri":"{}"}}"#,
+                            json_encode(acn), json_encode(&otpauth::build(&name, acn, account)))).collect::<Vec<_>>().join(","));
+                        json = &res
+                    }
+                }
+            } else {
+                json = r#"{"error":"No such namespace."}"#;
+            }
+        }
+        "excv" => { // export accounts as CSV
+            if password_confirmed(&web, &password) {
+                let csv = csv::export(&namespaces);
+                if cli {
+                    eprint!("{csv}")
+                }
+                res = format!(r#"{{"csv":"{}"}}"#, json_encode(&csv));
+                json = &res
+            } else {
+                json = r#"{"error":"The password isn't confirmed."}"#;
+            }
+        }
+        "upcv" => { // import accounts of an uploaded CSV
+            match web.param("upFile") {
+                None => json = r#"{"error":"nothing was uploaded"}"#,
+                Some(file) => {
+                    let up_file = PathBuf::from(&file);
+                    let data = fs::read_to_string(&up_file);
+                    if !cli {
+                        let _ = fs::remove_file(up_file);
+                    }
+                    code_str = match data.map_err(|err| TOTPError::new(err.to_string()))
+                        .and_then(|data| csv::entries(&data, &web.param("map").unwrap_or_default())) {
+                        Ok(entries) => {
+                            update_db = !dry_run(&web);
+                            let import = Import::new(entries, web.param("name").filter(|name| !name.is_empty()),
+                                "no namespace or issuer, specify a namespace", &namespaces);
+                            format!(r#"{{"ok":true,{}}}"#, apply_import(&web, &mut namespaces, import))
+                        }
+                        Err(err) => format!(r#"{{"error":"The CSV can't be read: {}"}}"#, json_encode(&err.cause)),
+                    };
+                    json = &code_str
+                }
+            }
+        }
+        "expm" => { // export an account as Google Authenticator PAM module file
+            if !password_confirmed(&web, &password) {
+                json = r#"{"error":"The password isn't confirmed."}"#;
+            } else if let Some(name) = web.param("name") &&
+                let Some(acn) = web.param("account") &&
+                let Some(account) = namespaces.get(&name).and_then(|ns| ns.get(&acn)) {
+                res = match pam::build(account) {
+                    Ok(file) => {
+                        if cli {
+                            eprint!("{file}")
+                        }
+                        format!(r#"{{"file":"{}"}}"#, json_encode(&file))
+                    }
+                    Err(err) => format!(r#"{{"error":"The PAM module can't use the account: {}"}}"#, json_encode(&err.cause)),
+                };
+                json = &res
+            } else {
+                json = r#"{"error":"No such account."}"#;
+            }
+        }
+        "uppm" => { // import an uploaded Google Authenticator PAM module file as an account
+            if let Some(name) = web.param("name").filter(|name| !name.is_empty()) &&
+                let Some(acn) = web.param("account").filter(|acn| !acn.is_empty()) &&
+                let Some(file) = web.param("upFile") {
+                let up_file = PathBuf::from(&file);
+                let data = fs::read_to_string(&up_file);
+                if !cli {
+                    let _ = fs::remove_file(up_file);
+                }
+                code_str = match data.map_err(|err| TOTPError::new(err.to_string())).and_then(|data| pam::parse(&data)) {
+                    // the secret of an account can't be replaced by mistake
+                    Ok(_) if namespaces.get(&name).is_some_and(|ns| ns.contains_key(&acn)) =>
+                        r#"{"error":"The account already exists, remove it first or choose another name."}"#.to_string(),
+                    Ok(account) => {
+                        let res = format!(r#"{{"ok":true,"name":"{}","account":"{}","scratch_codes":{}}}"#,
+                            json_encode(&name), json_encode(&acn), account.scratch_codes.len());
+                        namespaces.entry(name).or_default().insert(acn, account);
+                        update_db = true;
+                        res
+                    }
+                    Err(err) => format!(r#"{{"error":"The PAM module file can't be read: {}"}}"#, json_encode(&err.cause)),
+                };
+                json = &code_str
+            } else {
+                json = r#"{"error":"A namespace, an account and an uploaded file are required."}"#;
+            }
+        }
+        "qrcd" => { // QR code of an account otpauth URI
+            if !password_confirmed(&web, &password) {
+                json = r#"{"error":"The password isn't confirmed."}"#;
+            } else if let Some(name) = web.param("name") &&
+                let Some(acn) = web.param("account") &&
+                let Some(account) = namespaces.get(&name).and_then(|ns| ns.get(&acn)) {
+                match qrcode::QrCode::encode(otpauth::build(&name, &acn, account).as_bytes()) {
+                    Some(qr) => {
+                        // a terminal gets the text, and a browser the image
+                        res = if cli || web.param("format").as_deref() == Some("text") {
+                            let text = qr.to_unicode();
+                            if cli {
+                                eprint!("{text}")
+                            }
+                            format!(r#"{{"text":"{}"}}"#, json_encode(&text))
+                        } else {
+                            format!(r#"{{"svg":"{}"}}"#, json_encode(&qr.to_svg()))
+                        };
+                        json = &res
+                    }
+                    None => json = r#"{"error":"The URI is too long for a QR code."}"#,
+                }
+            } else {
+                json = r#"{"error":"No such account."}"#;
+            }
+        }
         "upse" => { // update a secret for an account
             if let Some(name) = web.param("name") &&
                 let Some(acn) = web.param("account") &&
                 let Some(secret) = web.param("secret") &&
                 let Some(ns) = namespaces.get_mut(&name) &&
                 !secret.is_empty() {
-                    // TODO if no such account
-                    ns.insert(acn, secret);
-                    update_db = true;
-                    json = r#"{"ok":true}"#;
+                    // an unknown account gets added as TOTP
+                    let mut account = ns.get(&acn).cloned().unwrap_or_default();
+                    account.secret = secret;
+                    // codes of a new secret weren't used yet
+                    account.last_used = None;
+                    if let Err(err) = apply_params(&web, &mut account) {
+                        json = err;
+                    } else {
+                        ns.insert(acn, account);
+                        update_db = true;
+                        json = r#"{"ok":true}"#;
+                    }
             } else {
                 json = r#"{"error":"Insufficient info to update the secret."}"#;
             }
@@ -299,6 +779,31 @@ fn main() -> Result<(), Box<dyn Error>> {
                  json = r#"{"error":"No update the account."}"#;
             }
         }
+        "cntr" => { // set or inspect a counter of HOTP account
+            if let Some(name) = web.param("name") &&
+                let Some(acn) = web.param("account") &&
+                let Some(ns) = namespaces.get_mut(&name) &&
+                let Some(account) = ns.get_mut(&acn) &&
+                let Some(counter) = account.counter {
+                match web.param("counter") {
+                    Some(new_counter) if !new_counter.is_empty() => {
+                        if let Ok(new_counter) = new_counter.parse::<u64>() {
+                            account.counter = Some(new_counter);
+                            update_db = true;
+                            json = r#"{"ok":true}"#;
+                        } else {
+                            json = r#"{"error":"The counter isn't a valid number."}"#;
+                        }
+                    }
+                    _ => {
+                        res = format!(r#"{{"counter":{counter}}}"#);
+                        json = &res
+                    }
+                }
+            } else {
+                json = r#"{"error":"No such HOTP account."}"#;
+            }
+        }
         "uppa" => { // update password
             if let Some(pass) = web.param("newpassword") {
                 if !pass.is_empty() {
@@ -312,7 +817,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         "dndb" => { // download db
             if let Some(dn_password) = web.param("dnpassword") {
-                let db = write_db(&dn_password, &namespaces);
+                let db = write_db(&dn_password, &namespaces, &container)?;
                 // Content-Lengt will be recalculated by CGI provider anyway
                 print!("Content-Length: {}\r\nContent-Type: application/octet-stream\r\nContent-Disposition: attachment; filename=\"totp.db\"\r\n\r\n", db.len());
                 io::stdout().write_all(&db[..])?
@@ -326,11 +831,23 @@ fn main() -> Result<(), Box<dyn Error>> {
                     let up_password = web.param("uppassword") .unwrap_or_default();
                     let up_file = PathBuf::from(&file);
                     match read_db(&up_file, &up_password) {
-                        Ok(new_namespaces) => {
-                            namespaces = new_namespaces;
-                            let _ = fs::remove_file(up_file);
-                            update_db = true;
-                            json = r#"{"ok":true}"#;
+                        // the uploaded DB replaces the current one, unless a merge is requested
+                        Ok((new_namespaces, _)) => match web.param("merge").filter(|merge| !merge.is_empty()) {
+                            None => {
+                                namespaces = new_namespaces;
+                                let _ = fs::remove_file(up_file);
+                                update_db = true;
+                                json = r#"{"ok":true}"#;
+                            }
+                            Some(merge) => match MergePolicy::parse(&merge) {
+                                Some(policy) => {
+                                    code_str = merge_db(&mut namespaces, new_namespaces, policy);
+                                    let _ = fs::remove_file(up_file);
+                                    update_db = true;
+                                    json = &code_str
+                                }
+                                None => json = r#"{"error":"The merge can be current, uploaded or both."}"#,
+                            }
                         }
                         Err(err) => {code_str = format!("Can't correctly read new DB {err}");
                             json = &code_str;},
@@ -338,6 +855,24 @@ fn main() -> Result<(), Box<dyn Error>> {
                 }
             }
         }
+        "dfdb" => { // compare an uploaded db with the current one
+            match web.param("upFile") {
+                None => json = r#"{"error":"nothing was uploaded"}"#,
+                Some(file) => {
+                    let up_file = PathBuf::from(&file);
+                    code_str = match read_db(&up_file, &web.param("uppassword").unwrap_or_default()) {
+                        Ok((uploaded, _)) => diff_db(&namespaces, &uploaded),
+                        Err(err) => format!(r#"{{"error":"Can't correctly read the uploaded DB: {}"}}"#, json_encode(&err.cause)),
+                    };
+                    if !cli {
+                        let _ = fs::remove_file(up_file);
+                    }
+                    // nothing gets stored, even a legacy DB migration
+                    update_db = false;
+                    json = &code_str
+                }
+            }
+        }
         _ => { // op error
             json = r#"{"error":"unknown op"}"#;
         }
@@ -346,12 +881,193 @@ fn main() -> Result<(), Box<dyn Error>> {
         json,
     }.show();
     if update_db {
-        Ok(fs::write(&home,write_db(&password, &namespaces))?)
+        store_db(&home, &password, &namespaces, &container, !counters_only)
     } else {
         Ok(())
     }
 }
 
+/// Writes the DB, the previous state is kept as a backup when requested. A failed backup doesn't prevent
+/// storing the changes.
+fn store_db(home: &PathBuf, password: &str, namespaces: &Namespaces, container: &Container, backup: bool) -> Result<(), Box<dyn Error>> {
+    if backup && let Err(err) = backup::rotate(home, backup::count(home)) {
+        eprintln!("No backup of the DB: {err}")
+    }
+    Ok(fs::write(home, write_db(password, namespaces, container)?)?)
+}
+
+/// Adds the account of otpauth URI, the namespace is the issuer and the account is the label name,
+/// unless they're specified. The result is a JSON response, an error one when nothing is added.
+fn add_uri(web: &simweb::WebData, namespaces: &mut Namespaces, uri: &str) -> Result<String, String> {
+    let otpauth = otpauth::parse(uri)
+        .map_err(|err| format!(r#"{{"error":"The URI is malformed: {}"}}"#, json_encode(&err.cause)))?;
+    let name = web.param("name").filter(|name| !name.is_empty()).or(otpauth.issuer)
+        .ok_or_else(|| r#"{"error":"The URI has no issuer, specify a namespace."}"#.to_string())?;
+    let acn = web.param("account").filter(|acn| !acn.is_empty()).unwrap_or(otpauth.name);
+    let res = format!(r#"{{"ok":true,"name":"{}","account":"{}"}}"#, json_encode(&name), json_encode(&acn));
+    namespaces.entry(name).or_default().insert(acn, otpauth.account);
+    Ok(res)
+}
+
+/// Adds accounts of Google Authenticator transfer URI, the namespace is the issuer, unless it's specified.
+/// The result is a JSON report of added and skipped accounts, or an error when the URI can't be read.
+fn import_migration(web: &simweb::WebData, namespaces: &mut Namespaces, uri: &str) -> Result<String, String> {
+    let batch = migration::parse(uri)
+        .map_err(|err| format!(r#"{{"error":"The URI is malformed: {}"}}"#, json_encode(&err.cause)))?;
+    let import = Import::new(batch.entries, web.param("name").filter(|name| !name.is_empty()),
+        "no issuer, specify a namespace", namespaces);
+    Ok(format!(r#"{{"ok":true,"batch":{},"batches":{},{}}}"#, batch.index + 1, batch.size, apply_import(web, namespaces, import)))
+}
+
+/// Adds accounts of an export of another authenticator, the format is recognized by the structure,
+/// unless the importer is given. Groups of an entry become namespaces, an entry without groups goes
+/// to the specified namespace, or to the issuer one. The result is a JSON report of added and skipped
+/// accounts, or an error when the export can't be read.
+fn import_export(web: &simweb::WebData, namespaces: &mut Namespaces, data: &str, importer: Option<&dyn Importer>) -> Result<String, String> {
+    let json = simjson::parse(data);
+    let importer = importer.or_else(|| import::detect(&json)).ok_or_else(|| format!(
+        r#"{{"error":"The export format isn't recognized, supported formats are {}."}}"#,
+        import::IMPORTERS.iter().map(|importer| importer.format()).collect::<Vec<_>>().join(", ")))?;
+    let entries = importer.entries(&json, &web.param("uppassword").unwrap_or_default())
+        .map_err(|err| format!(r#"{{"error":"The {} export can't be read: {}"}}"#, importer.format(), json_encode(&err.cause)))?;
+    let import = Import::new(entries, web.param("name").filter(|name| !name.is_empty()),
+        "no group or issuer, specify a namespace", namespaces);
+    Ok(format!(r#"{{"ok":true,"format":"{}",{}}}"#, importer.format(), apply_import(web, namespaces, import)))
+}
+
+/// Adds imported accounts, unless it's a dry run requested by `dry=true` which only previews them.
+/// The result is JSON fields of the report listing added and skipped accounts, a skipped one has a reason.
+fn apply_import(web: &simweb::WebData, namespaces: &mut Namespaces, import: Import) -> String {
+    let dry = dry_run(web);
+    let added = import.added.iter().map(|(name, acn)| format!(r#"{{"name":"{}","account":"{}"}}"#,
+        json_encode(name), json_encode(acn))).collect::<Vec<_>>().join(",");
+    let skipped = import.skipped.iter().map(|(name, acn, reason)| format!(r#"{{"name":"{}","account":"{}","reason":"{}"}}"#,
+        json_encode(name), json_encode(acn), json_encode(reason))).collect::<Vec<_>>().join(",");
+    if !dry {
+        // imported names aren't taken by current accounts
+        for (name, accounts) in import.namespaces {
+            namespaces.entry(name).or_default().extend(accounts)
+        }
+    }
+    format!(r#""dry":{dry},"added":[{added}],"skipped":[{skipped}]"#)
+}
+
+fn dry_run(web: &simweb::WebData) -> bool {
+    web.param("dry").as_deref() == Some("true")
+}
+
+/// The way to resolve a conflict of an uploaded account with a different current one.
+#[derive(Clone, Copy, PartialEq)]
+enum MergePolicy {
+    /// keep the current account
+    Current,
+    /// replace the current account by the uploaded one
+    Uploaded,
+    /// keep the current account and add the uploaded one under a new name
+    Both,
+}
+
+impl MergePolicy {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "current" => Some(MergePolicy::Current),
+            "uploaded" => Some(MergePolicy::Uploaded),
+            "both" => Some(MergePolicy::Both),
+            _ => None,
+        }
+    }
+}
+
+/// Merges accounts of the uploaded DB into the current ones, an account which generates other codes
+/// than the current one of the same name is a conflict resolved by the policy. The result is a JSON
+/// report of added, updated and conflicting accounts.
+fn merge_db(namespaces: &mut Namespaces, uploaded: Namespaces, policy: MergePolicy) -> String {
+    let (mut added, mut updated, mut conflicts) = (Vec::new(), Vec::new(), Vec::new());
+    let mut uploaded: Vec<_> = uploaded.into_iter().flat_map(|(name, accounts)|
+        accounts.into_iter().map(move |(acn, account)| (name.clone(), acn, account))).collect();
+    uploaded.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    for (name, acn, account) in uploaded {
+        let names = format!(r#""name":"{}","account":"{}""#, json_encode(&name), json_encode(&acn));
+        let ns = namespaces.entry(name).or_default();
+        match ns.get(&acn) {
+            None => {
+                added.push(format!("{{{names}}}"));
+                ns.insert(acn, account);
+            }
+            Some(current) if current.same_codes(&account) => (),
+            Some(_) => match policy {
+                MergePolicy::Current => conflicts.push(format!(r#"{{{names},"resolution":"current kept"}}"#)),
+                MergePolicy::Uploaded => {
+                    conflicts.push(format!(r#"{{{names},"resolution":"uploaded taken"}}"#));
+                    updated.push(format!("{{{names}}}"));
+                    ns.insert(acn, account);
+                }
+                MergePolicy::Both => {
+                    let renamed = (2..).map(|i| format!("{acn} ({i})")).find(|renamed| !ns.contains_key(renamed)).unwrap();
+                    conflicts.push(format!(r#"{{{names},"resolution":"uploaded added as","renamed":"{}"}}"#, json_encode(&renamed)));
+                    ns.insert(renamed, account);
+                }
+            }
+        }
+    }
+    format!(r#"{{"ok":true,"added":[{}],"updated":[{}],"conflicts":[{}]}}"#, added.join(","), updated.join(","), conflicts.join(","))
+}
+
+/// Compares the uploaded DB with the current one as changes made by restoring it, added namespaces
+/// and accounts are only uploaded ones, removed are only current ones. Accounts of both with different
+/// secrets are changed ones, the secrets aren't revealed. The result is a JSON report.
+fn diff_db(namespaces: &Namespaces, uploaded: &Namespaces) -> String {
+    let sorted = |mut list: Vec<String>| { list.sort(); list.join(",") };
+    let names = |name: &str, acn: &str| format!(r#"{{"name":"{}","account":"{}"}}"#, json_encode(name), json_encode(acn));
+    let ns_only = |a: &Namespaces, b: &Namespaces| sorted(a.keys().filter(|name| !b.contains_key(*name))
+        .map(|name| format!(r#""{}""#, json_encode(name))).collect());
+    let accounts_only = |a: &Namespaces, b: &Namespaces| sorted(a.iter().flat_map(|(name, accounts)| accounts.keys()
+        .filter(|acn| !b.get(name).is_some_and(|ns| ns.contains_key(*acn))).map(|acn| names(name, acn))).collect());
+    let changed = sorted(uploaded.iter().flat_map(|(name, accounts)| accounts.iter()
+        .filter(|(acn, account)| namespaces.get(name).and_then(|ns| ns.get(*acn)).is_some_and(|current| current.secret != account.secret))
+        .map(|(acn, _)| names(name, acn))).collect());
+    format!(r#"{{"ok":true,"namespaces":{{"added":[{}],"removed":[{}]}},"accounts":{{"added":[{}],"removed":[{}],"changed":[{changed}]}}}}"#,
+        ns_only(uploaded, namespaces), ns_only(namespaces, uploaded), accounts_only(uploaded, namespaces), accounts_only(namespaces, uploaded))
+}
+
+/// Secrets get revealed only when the password is typed again as `confirm` parameter.
+fn password_confirmed(web: &simweb::WebData, password: &str) -> bool {
+    web.param("confirm").is_some_and(|confirm| constant_time_eq(confirm.as_bytes(), password.as_bytes()))
+}
+
+/// Updates the account with optional parameters of the request, the parameters which aren't
+/// provided keep their values. An error is returned as a JSON response.
+fn apply_params(web: &simweb::WebData, account: &mut Account) -> Result<(), &'static str> {
+    let param = |name| web.param(name).filter(|value| !value.is_empty());
+    match param("type").as_deref() {
+        Some("hotp") => { account.counter.get_or_insert(0); }
+        Some("totp") => account.counter = None,
+        Some(_) => return Err(r#"{"error":"The type can be totp or hotp."}"#),
+        None => ()
+    }
+    if let Some(counter) = param("counter") && account.counter.is_some() {
+        account.counter = Some(counter.parse().map_err(|_| r#"{"error":"The counter isn't a valid number."}"#)?)
+    }
+    if let Some(digits) = param("digits") {
+        account.digits = digits.parse().ok().filter(|digits| (MIN_DIGITS..=MAX_DIGITS).contains(digits))
+            .ok_or(r#"{"error":"The digits value has to be a number from 6 to 10."}"#)?
+    }
+    if let Some(period) = param("period") {
+        account.period = period.parse().ok().filter(|&period| period > 0)
+            .ok_or(r#"{"error":"The period has to be a positive number of seconds."}"#)?
+    }
+    if let Some(algorithm) = param("algorithm") {
+        account.algorithm = Algorithm::parse(&algorithm).ok_or(r#"{"error":"Unsupported algorithm."}"#)?
+    }
+    if let Some(encoding) = param("encoding") {
+        account.encoding = Encoding::parse(&encoding).ok_or(r#"{"error":"The encoding can be decimal or steam."}"#)?
+    }
+    if let Some(t0) = param("t0") {
+        account.t0 = t0.parse().map_err(|_| r#"{"error":"The T0 isn't a valid Unix time."}"#)?
+    }
+    Ok(())
+}
+
 impl simweb::WebPage for Response<'_> { 
     fn main_load(&self) -> Result<String, Box<dyn std::error::Error + 'static>> {
         Ok(self.json.to_string ())
@@ -361,16 +1077,13 @@ impl simweb::WebPage for Response<'_> {
     }
 }
 
-fn read_db<'a>(home: &'a PathBuf, password: &'a str) -> Result<HashMap<String, HashMap<String,String>>, TOTPError> {
+fn read_db<'a>(home: &'a PathBuf, password: &'a str) -> Result<(Namespaces, Container), TOTPError> {
     let mut res = HashMap::new();
+    let mut container = Container::new();
     match fs::read(home) {
-        Ok(mut data) => {
-            let password = password.as_bytes();
-            if !password.is_empty() {
-                for i in 0..data.len() {
-                    data[i] ^= password[i % password.len()]
-                }
-            }
+        Ok(data) => {
+            let (data, db_container) = dbcrypt::decrypt(password, &data)?;
+            container = db_container;
             //eprintln!("{}", String::from_utf8_lossy(&data));
             let json_db = simjson::parse(&String::from_utf8_lossy(&data));
             match json_db {
@@ -381,45 +1094,207 @@ fn read_db<'a>(home: &'a PathBuf, password: &'a str) -> Result<HashMap<String, H
                             let mut a_res = HashMap::new();
                             for (a_key, a_value) in acn.iter() {
                                 if a_key.is_empty() { continue }
-                                if let JsonData::Text(secret) = a_value {
-                                     a_res.insert(a_key.to_string(), secret.to_string());
-                                 }
+                                let account = Account::from_json(a_value).map_err(|reason|
+                                    TOTPError::new(format!("the account {a_key} of {key} namespace can't be read: {reason}")))?;
+                                a_res.insert(a_key.to_string(), account);
                             }
                             res.insert(key.to_string(), a_res);
                         }
                     }
                 }
-                JsonData::Err(reason) => return Err(TOTPError{cause:format!("corrupted DB, probably wrong password: {reason}")}),
-                _ => return Err(TOTPError{cause:"unexpected data organization".to_string()})
+                JsonData::Err(reason) if container.legacy => return Err(TOTPError::new(format!("corrupted DB, probably wrong password: {reason}"))),
+                JsonData::Err(reason) => return Err(TOTPError::new(format!("corrupted DB: {reason}"))),
+                _ => return Err(TOTPError::new("unexpected data organization"))
             }
         }
          _ => eprintln!("new DB created")
     }
-    Ok(res)
+    Ok((res, container))
 }
 
-fn write_db(password: &str, db: &HashMap<String, HashMap<String,String>>) -> Vec<u8> {
+fn write_db(password: &str, db: &Namespaces, container: &Container) -> Result<Vec<u8>, TOTPError> {
     let mut res = String::from("{");
     for (key, value) in db.iter() {
         if key.is_empty() { continue }
-        write!(res,r#""{key}":{{"#).unwrap();
-        for (acn, secret) in value.iter() {
+        write!(res,r#""{}":{{"#, json_encode(key)).unwrap();
+        for (acn, account) in value.iter() {
             if !acn.is_empty() {
-                 write!(res,r#""{acn}":"{secret}","#).unwrap();
+                 write!(res,r#""{}":{},"#, json_encode(acn), account.to_json()).unwrap();
             }
         }
         // no json encodibg
         write!(res,r#""":""}},"#).unwrap();
     }
     write!(res,r#""":{{}} }}"#).unwrap();
-    let password = password.as_bytes();
-    let mut byte_vec: Vec<u8> = res.into_bytes();
-    if !password.is_empty() {
-        for i in 0..byte_vec.len() {
-            byte_vec[i] ^= password[i % password.len()]
-        } 
-    }
-    byte_vec
+    dbcrypt::encrypt(password, res.as_bytes(), container)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 4226 test values of the secret "12345678901234567890"
+    const HOTP: [&str; 10] = ["755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871", "520489"];
+
+    #[test]
+    fn hotp() {
+        for (counter, code) in HOTP.iter().enumerate() {
+            assert_eq!(generate_hotp(b"12345678901234567890", 6, counter as u64, Algorithm::Sha1, Encoding::Decimal), *code);
+        }
+    }
+
+    #[test]
+    fn verify_window() {
+        let key = OtpKey::new(b"12345678901234567890", Algorithm::Sha1);
+        assert_eq!(verify_code(&key, HOTP[5], 6, Encoding::Decimal, 5, -1..=1), Some(0));
+        assert_eq!(verify_code(&key, HOTP[4], 6, Encoding::Decimal, 5, -1..=1), Some(-1));
+        assert_eq!(verify_code(&key, HOTP[6], 6, Encoding::Decimal, 5, -1..=1), Some(1));
+        assert_eq!(verify_code(&key, HOTP[3], 6, Encoding::Decimal, 5, -1..=1), None);
+        assert_eq!(verify_code(&key, HOTP[7], 6, Encoding::Decimal, 5, -1..=1), None);
+        // HOTP codes are looked ahead only
+        assert_eq!(verify_code(&key, HOTP[4], 6, Encoding::Decimal, 5, 0..=3), None);
+        assert_eq!(verify_code(&key, HOTP[8], 6, Encoding::Decimal, 5, 0..=3), Some(3));
+        // offsets before the first moving factor are skipped
+        assert_eq!(verify_code(&key, HOTP[0], 6, Encoding::Decimal, 0, -2..=2), Some(0));
+        assert_eq!(verify_code(&key, &HOTP[0][1..], 6, Encoding::Decimal, 0, 0..=0), None);
+    }
+
+    #[test]
+    fn replayed_codes() {
+        let secret = b"12345678901234567890";
+        let mut account = Account { digits: 8, ..Account::new(String::new()) };
+        // RFC 6238 codes of steps 37037035 and 37037036
+        let (valid, invalid, used) = (r#"{"valid":true,"offset":0}"#, r#"{"valid":false}"#, r#"{"valid":false,"error":"The code has been already used."}"#);
+        assert_eq!(verify_account(&mut account, secret, "07081804", 1, Some(1111111109)), (valid.to_string(), true));
+        assert_eq!(account.last_used, Some(37037036));
+        assert_eq!(verify_account(&mut account, secret, "07081804", 1, Some(1111111109)), (used.to_string(), false));
+        // a code of an earlier step of the window is a replay too
+        let earlier = generate_totp_at(secret, 8, 30, 0, Algorithm::Sha1, Encoding::Decimal, 1111111079).unwrap().0;
+        assert_eq!(verify_account(&mut account, secret, &earlier, 1, Some(1111111109)), (used.to_string(), false));
+        assert_eq!(verify_account(&mut account, secret, "07081804", 1, None), (invalid.to_string(), false));
+        let next = generate_totp_at(secret, 8, 30, 0, Algorithm::Sha1, Encoding::Decimal, 1111111110).unwrap().0;
+        assert_eq!(verify_account(&mut account, secret, &next, 1, Some(1111111109)), (r#"{"valid":true,"offset":1}"#.to_string(), true));
+        assert_eq!(account.last_used, Some(37037037));
+    }
+
+    #[test]
+    fn hotp_resync() {
+        let secret = b"12345678901234567890";
+        let mut account = Account { counter: Some(2), ..Account::new(String::new()) };
+        // a code ahead within the window moves the counter after it
+        assert_eq!(verify_account(&mut account, secret, HOTP[5], 3, None), (r#"{"valid":true,"offset":3}"#.to_string(), true));
+        assert_eq!((account.counter, account.last_used), (Some(6), Some(5)));
+        for code in [HOTP[5], HOTP[4]] {
+            assert!(!verify_account(&mut account, secret, code, 3, None).1);
+        }
+        assert_eq!(verify_account(&mut account, secret, HOTP[9], 3, None), (r#"{"valid":true,"offset":3}"#.to_string(), true));
+        assert_eq!(verify_account(&mut account, secret, HOTP[7], 3, None).0, r#"{"valid":false,"error":"The code has been already used."}"#);
+        assert_eq!(account.counter, Some(10));
+    }
+
+    #[test]
+    fn hmac_sha1() {
+        let test = Hmac::<Sha1>::new(b"key").mac(b"The quick brown fox jumps over the lazy dog");
+        assert_eq!(simweb::to_hex(&test), "de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9");
+    }
+
+    /// RFC 6238 test vectors
+    #[test]
+    fn totp() {
+        let totp_at = |algorithm, secret: &[u8], time| generate_totp_at(secret, 8, 30, 0, algorithm, Encoding::Decimal, time).map(|(code, _)| code);
+        assert_eq!(totp_at(Algorithm::Sha1, b"12345678901234567890", 59).as_deref(), Some("94287082"));
+        assert_eq!(totp_at(Algorithm::Sha1, b"12345678901234567890", 1111111109).as_deref(), Some("07081804"));
+        assert_eq!(generate_totp_at(b"12345678901234567890", 10, 30, 0, Algorithm::Sha1, Encoding::Decimal, 59).map(|(code, _)| code).as_deref(), Some("1094287082"));
+        assert_eq!(totp_at(Algorithm::Sha256, b"12345678901234567890123456789012", 1234567890).as_deref(), Some("91819424"));
+        assert_eq!(totp_at(Algorithm::Sha512, b"1234567890123456789012345678901234567890123456789012345678901234", 20000000000).as_deref(), Some("47863826"));
+    }
+
+    #[test]
+    fn steam() {
+        let steam_at = |time| generate_totp_at(b"12345678901234567890", 6, 30, 0, Algorithm::Sha1, Encoding::Steam, time).map(|(code, _)| code);
+        assert_eq!(steam_at(59).as_deref(), Some("PV9M4"));
+        assert_eq!(steam_at(1111111109).as_deref(), Some("PY4YB"));
+    }
+
+    fn namespaces(accounts: &[(&str, &str, &str)]) -> Namespaces {
+        let mut res: Namespaces = HashMap::new();
+        for (name, acn, secret) in accounts {
+            res.entry(name.to_string()).or_default().insert(acn.to_string(), Account::new(secret.to_string()));
+        }
+        res
+    }
+
+    #[test]
+    fn merge_policies() {
+        let current = [("Work", "john", "JBSWY3DPEHPK3PXP"), ("Work", "jane", "GEZDGNBVGY3TQOJQ"), ("Work", "jane (2)", "MFRGGZDF")];
+        let uploaded = [("Work", "john", "JBSWY3DPEHPK3PXP"), ("Work", "jane", "MZXW6YTB"), ("Home", "mary", "ONSWG4TF")];
+        let secret = |namespaces: &Namespaces, name: &str, acn: &str| namespaces[name].get(acn).map(|account| account.secret.clone());
+
+        let mut merged = namespaces(&current);
+        assert_eq!(merge_db(&mut merged, namespaces(&uploaded), MergePolicy::Current),
+            r#"{"ok":true,"added":[{"name":"Home","account":"mary"}],"updated":[],"conflicts":[{"name":"Work","account":"jane","resolution":"current kept"}]}"#);
+        assert_eq!(secret(&merged, "Work", "jane").as_deref(), Some("GEZDGNBVGY3TQOJQ"));
+        assert_eq!(secret(&merged, "Home", "mary").as_deref(), Some("ONSWG4TF"));
+
+        let mut merged = namespaces(&current);
+        assert_eq!(merge_db(&mut merged, namespaces(&uploaded), MergePolicy::Uploaded),
+            r#"{"ok":true,"added":[{"name":"Home","account":"mary"}],"updated":[{"name":"Work","account":"jane"}],"conflicts":[{"name":"Work","account":"jane","resolution":"uploaded taken"}]}"#);
+        assert_eq!(secret(&merged, "Work", "jane").as_deref(), Some("MZXW6YTB"));
+
+        let mut merged = namespaces(&current);
+        assert_eq!(merge_db(&mut merged, namespaces(&uploaded), MergePolicy::Both),
+            r#"{"ok":true,"added":[{"name":"Home","account":"mary"}],"updated":[],"conflicts":[{"name":"Work","account":"jane","resolution":"uploaded added as","renamed":"jane (3)"}]}"#);
+        assert_eq!((secret(&merged, "Work", "jane").as_deref(), secret(&merged, "Work", "jane (3)").as_deref()),
+            (Some("GEZDGNBVGY3TQOJQ"), Some("MZXW6YTB")));
+        assert_eq!(merged["Work"].len(), 4);
+        assert!(MergePolicy::parse("theirs").is_none());
+    }
+
+    #[test]
+    fn db_diff() {
+        let current = namespaces(&[("Work", "john", "JBSWY3DPEHPK3PXP"), ("Work", "jane", "GEZDGNBVGY3TQOJQ"), ("Old", "bob", "MFRGGZDF")]);
+        let uploaded = namespaces(&[("Work", "john", "JBSWY3DPEHPK3PXP"), ("Work", "jane", "MZXW6YTB"), ("Work", "mary", "ONSWG4TF"),
+            ("Home \"1\"", "max", "MFRGGZDF")]);
+        assert_eq!(diff_db(&current, &uploaded), concat!(r#"{"ok":true,"namespaces":{"added":["Home \"1\""],"removed":["Old"]},"#,
+            r#""accounts":{"added":[{"name":"Home \"1\"","account":"max"},{"name":"Work","account":"mary"}],"removed":[{"name":"Old","account":"bob"}],"#,
+            r#""changed":[{"name":"Work","account":"jane"}]}}"#));
+        assert_eq!(diff_db(&current, &current),
+            r#"{"ok":true,"namespaces":{"added":[],"removed":[]},"accounts":{"added":[],"removed":[],"changed":[]}}"#);
+    }
+
+    #[test]
+    fn time_step_bounds() {
+        assert!(time_step_at(30, 100, 99).is_none());
+        assert!(time_step_at(u64::MAX, 1, u64::MAX - 1).is_none());
+        let step = time_step_at(30, 0, 1111111109).unwrap();
+        assert_eq!((step.counter, step.start, step.end, step.remaining()), (37037036, 1111111080, 1111111110, 1));
+    }
+
+    #[test]
+    fn db_names_round_trip() {
+        let (name, acn) = (r#"Q"uo\te"#.to_string(), r#"CORP\john "the" admin"#.to_string());
+        let mut namespaces = Namespaces::new();
+        namespaces.entry(name.clone()).or_default().insert(acn.clone(), Account::new("JBSWY3DPEHPK3PXP".to_string()));
+        let path = env::temp_dir().join(format!("simtotp-test-{}.db", std::process::id()));
+        fs::write(&path, write_db("pw", &namespaces, &Container::new()).unwrap()).unwrap();
+        let read = read_db(&path, "pw");
+        let _ = fs::remove_file(&path);
+        let (read, _) = read.unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[&name].keys().collect::<Vec<_>>(), [&acn]);
+        assert_eq!(read[&name][&acn].secret, "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn unreadable_account() {
+        let json = br#"{"Work":{"john":{"secret":"JBSWY3DPEHPK3PXP"},"jane":{"secret":"GEZDGNBV","algorithm":"SHA3"}}}"#;
+        let path = env::temp_dir().join(format!("simtotp-unreadable-{}.db", std::process::id()));
+        fs::write(&path, dbcrypt::encrypt("pw", json, &Container::new()).unwrap()).unwrap();
+        let read = read_db(&path, "pw");
+        let _ = fs::remove_file(&path);
+        // the DB isn't read without the account, otherwise a write would drop it
+        assert_eq!(read.err().unwrap().cause, "the account jane of Work namespace can't be read: SHA3 algorithm isn't supported");
+    }
 }
 
 /*
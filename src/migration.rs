@@ -0,0 +1,239 @@
+/*
+Google Authenticator accounts transfer
+
+    otpauth-migration://offline?data=DATA     // DATA is URL encoded base64 of MigrationPayload protobuf
+
+    message MigrationPayload {
+        repeated OtpParameters otp_parameters = 1;
+        int32 version = 2;
+        int32 batch_size = 3;       // a large export is split in several codes
+        int32 batch_index = 4;
+        int32 batch_id = 5;
+    }
+    message OtpParameters {
+        bytes secret = 1;
+        string name = 2;
+        string issuer = 3;
+        Algorithm algorithm = 4;    // 0 - unspecified, 1 - SHA1, 2 - SHA256, 3 - SHA512, 4 - MD5
+        DigitCount digits = 5;      // 0 - unspecified, 1 - six, 2 - eight
+        OtpType type = 6;           // 0 - unspecified, 1 - HOTP, 2 - TOTP
+        int64 counter = 7;
+    }
+
+    protobuf wire format: key varint (field number << 3 | wire type) followed by
+        0 - varint, 1 - 8 bytes, 2 - length varint and bytes, 5 - 4 bytes
+*/
+use crate::{TOTPError, account::{Account, Algorithm}, import::Entry, otpauth::percent_decode};
+use base32::Alphabet;
+
+const PREFIX: &str = "otpauth-migration://offline?";
+
+/// Entries of one code of the export.
+pub struct Batch {
+    pub entries: Vec<Entry>,
+    pub index: u64,
+    pub size: u64,
+}
+
+pub fn is_migration(uri: &str) -> bool {
+    uri.trim().to_ascii_lowercase().starts_with("otpauth-migration:")
+}
+
+/// Parses the transfer URI.
+pub fn parse(uri: &str) -> Result<Batch, TOTPError> {
+    let uri = uri.trim();
+    if !uri.get(..PREFIX.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(PREFIX)) {
+        return Err(TOTPError::new(format!("the URI has to start with {PREFIX}")))
+    }
+    let data = uri[PREFIX.len()..].split('&').find_map(|param| param.strip_prefix("data="))
+        .ok_or_else(|| TOTPError::new("the URI has no data parameter"))?;
+    let payload = base64_decode(&percent_decode(data)?).ok_or_else(|| TOTPError::new("the data isn't valid base64"))?;
+
+    let mut res = Batch { entries: Vec::new(), index: 0, size: 1 };
+    let mut reader = Reader { data: &payload, pos: 0 };
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(parameters)) => res.entries.push(parse_parameters(parameters)?),
+            (3, Value::Varint(size)) => res.size = size,
+            (4, Value::Varint(index)) => res.index = index,
+            // the version and the batch id aren't needed
+            _ => (),
+        }
+    }
+    Ok(res)
+}
+
+fn parse_parameters(data: &[u8]) -> Result<Entry, TOTPError> {
+    let (mut secret, mut name, mut issuer) = (Vec::new(), String::new(), String::new());
+    let (mut algorithm, mut digits, mut kind, mut counter) = (0, 0, 0, 0);
+    let mut reader = Reader { data, pos: 0 };
+    let text = |bytes: &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| TOTPError::new("an account name isn't UTF-8"));
+    while let Some((field, value)) = reader.field()? {
+        match (field, value) {
+            (1, Value::Bytes(bytes)) => secret = bytes.to_vec(),
+            (2, Value::Bytes(bytes)) => name = text(bytes)?,
+            (3, Value::Bytes(bytes)) => issuer = text(bytes)?,
+            (4, Value::Varint(value)) => algorithm = value,
+            (5, Value::Varint(value)) => digits = value,
+            (6, Value::Varint(value)) => kind = value,
+            (7, Value::Varint(value)) => counter = value,
+            _ => (),
+        }
+    }
+    // the name can be a label with the issuer
+    if let Some((prefix, account)) = name.split_once(':') && (issuer.is_empty() || prefix.trim() == issuer) {
+        if issuer.is_empty() {
+            issuer = prefix.trim().to_string()
+        }
+        name = account.trim().to_string()
+    }
+
+    let issuer = Some(issuer).filter(|issuer| !issuer.is_empty());
+    let account = to_account(&secret, algorithm, digits, kind, counter)
+        .map(|account| Account { issuer: issuer.clone(), ..account });
+    // the export has no groups
+    Ok(Entry { groups: Vec::new(), issuer, name, account })
+}
+
+/// Maps OtpParameters values, unspecified ones get defaults of Google Authenticator.
+fn to_account(secret: &[u8], algorithm: u64, digits: u64, kind: u64, counter: u64) -> Result<Account, String> {
+    if secret.is_empty() {
+        return Err("no secret".to_string())
+    }
+    let mut res = Account::new(base32::encode(Alphabet::Rfc4648 { padding: false }, secret));
+    res.algorithm = match algorithm {
+        0 | 1 => Algorithm::Sha1,
+        2 => Algorithm::Sha256,
+        3 => Algorithm::Sha512,
+        4 => return Err("MD5 algorithm isn't supported".to_string()),
+        _ => return Err(format!("unknown algorithm {algorithm}")),
+    };
+    res.digits = match digits {
+        0 | 1 => 6,
+        2 => 8,
+        _ => return Err(format!("unknown digit count {digits}")),
+    };
+    match kind {
+        0 | 2 => (),
+        1 => res.counter = Some(counter),
+        _ => return Err(format!("unknown OTP type {kind}")),
+    }
+    Ok(res)
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Minimal protobuf reader, values of fixed size wire types are skipped.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn varint(&mut self) -> Result<u64, TOTPError> {
+        let mut res = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = *self.data.get(self.pos).ok_or_else(|| TOTPError::new("truncated protobuf data"))?;
+            self.pos += 1;
+            res |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(res)
+            }
+        }
+        Err(TOTPError::new("too long protobuf varint"))
+    }
+
+    /// The next field number and its value, `None` at the end of the data.
+    fn field(&mut self) -> Result<Option<(u64, Value<'a>)>, TOTPError> {
+        loop {
+            if self.pos >= self.data.len() {
+                return Ok(None)
+            }
+            let key = self.varint()?;
+            let skip = match key & 7 {
+                0 => return Ok(Some((key >> 3, Value::Varint(self.varint()?)))),
+                2 => {
+                    let len = self.varint()? as usize;
+                    let bytes = self.data.get(self.pos..self.pos.saturating_add(len)).ok_or_else(|| TOTPError::new("truncated protobuf data"))?;
+                    self.pos += len;
+                    return Ok(Some((key >> 3, Value::Bytes(bytes))))
+                }
+                1 => 8,
+                5 => 4,
+                wire => return Err(TOTPError::new(format!("unsupported protobuf wire type {wire}"))),
+            };
+            if self.pos + skip > self.data.len() {
+                return Err(TOTPError::new("truncated protobuf data"))
+            }
+            self.pos += skip
+        }
+    }
+}
+
+/// Decodes base64 of the standard or URL safe alphabet, the padding is optional.
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut res = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        acc = acc << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((acc >> bits) as u8)
+        }
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a TOTP account with a label name, an HOTP one and an MD5 one in the second code of two
+    const URI: &str = "otpauth-migration://offline?data=CjUKCkhlbGxvId6tvu8SGEV4YW1wbGU6YWxpY2VAZ29vZ2xlLmNvbRoHRXhhbXBsZSABKAEwAgojChQxMjM0NTY3ODkwMTIzNDU2Nzg5MBIDYm9iIAIoAjABOAUKDAoDYWJjEgNtZDUgBBABGAIgASj%2F%2F%2F%2F%2F%2F%2F%2F%2F%2F%2F8B";
+
+    #[test]
+    fn parse_payload() {
+        let batch = parse(URI).unwrap();
+        assert_eq!((batch.index, batch.size), (1, 2));
+        let [alice, bob, md5] = &batch.entries[..] else { panic!("3 entries expected") };
+        assert_eq!((alice.issuer.as_deref(), alice.name.as_str()), (Some("Example"), "alice@google.com"));
+        let account = alice.account.as_ref().unwrap();
+        assert_eq!((account.secret.as_str(), account.digits, account.counter), ("JBSWY3DPEHPK3PXP", 6, None));
+        assert_eq!((bob.issuer.as_deref(), bob.name.as_str()), (None, "bob"));
+        let account = bob.account.as_ref().unwrap();
+        assert_eq!(account.secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!((account.algorithm, account.digits, account.counter), (Algorithm::Sha256, 8, Some(5)));
+        assert_eq!(md5.account.as_ref().err().map(String::as_str), Some("MD5 algorithm isn't supported"));
+    }
+
+    #[test]
+    fn malformed_payloads() {
+        assert!(parse("otpauth://totp/john?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert_eq!(parse("otpauth-migration://offlineé?data=").err().unwrap().cause, "the URI has to start with otpauth-migration://offline?");
+        assert!(parse("otpauth-migration://offline?").is_err());
+        assert!(parse("otpauth-migration://offline?data=%%%").is_err());
+        // truncated protobuf data is an error or the end of fields, but never a panic
+        let data = base64_decode(&percent_decode(&URI[URI.find("data=").unwrap() + 5..]).unwrap()).unwrap();
+        for len in 0..data.len() {
+            let mut reader = Reader { data: &data[..len], pos: 0 };
+            while let Some((_, value)) = reader.field().transpose().and_then(Result::ok) {
+                if let Value::Bytes(parameters) = value {
+                    let _ = parse_parameters(parameters);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+/*
+Key URI format
+
+    otpauth://TYPE/LABEL?PARAMETERS
+
+    TYPE        totp or hotp
+    LABEL       accountname or issuer:accountname, URL encoded, the colon can be encoded as %3A
+    PARAMETERS  secret    - base32 encoded secret, required
+                issuer    - the provider name, it should match the label issuer when both present
+                algorithm - SHA1 (default), SHA256 or SHA512
+                digits    - 6 (default) or 8
+                counter   - the initial counter, required for hotp
+                period    - the time step in seconds, 30 by default
+                encoder   - steam for Steam Guard codes, not a part of the original format
+*/
+use crate::{TOTPError, account::{Account, Algorithm, Encoding, MIN_DIGITS, MAX_DIGITS}};
+use base32::Alphabet;
+
+const SCHEME: &str = "otpauth://";
+
+/// An account described by a Key URI.
+pub struct OtpAuth {
+    pub issuer: Option<String>,
+    /// the account name part of the label
+    pub name: String,
+    pub account: Account,
+}
+
+pub fn is_otpauth(uri: &str) -> bool {
+    uri.trim().get(..SCHEME.len()).is_some_and(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+}
+
+/// Parses a Key URI, the error tells what exactly is wrong in it.
+pub fn parse(uri: &str) -> Result<OtpAuth, TOTPError> {
+    let uri = uri.trim();
+    if !is_otpauth(uri) {
+        return Err(TOTPError::new(format!("the URI has to start with {SCHEME}")))
+    }
+    let rest = &uri[SCHEME.len()..];
+    let (path, query) = rest.split_once('?').ok_or_else(|| TOTPError::new("the URI has no parameters"))?;
+    let (kind, label) = path.split_once('/').ok_or_else(|| TOTPError::new("the URI has no label"))?;
+
+    let mut account = Account::default();
+    match kind.to_ascii_lowercase().as_str() {
+        "totp" => (),
+        "hotp" => account.counter = Some(0),
+        // some authenticators use the type for Steam Guard accounts
+        "steam" => account.encoding = Encoding::Steam,
+        _ => return Err(TOTPError::new(format!("unsupported OTP type '{kind}', it can be totp or hotp"))),
+    }
+
+    let label = percent_decode(label)?;
+    let (label_issuer, name) = match label.split_once(':') {
+        Some((issuer, name)) => (Some(issuer.trim().to_string()), name.trim().to_string()),
+        None => (None, label.trim().to_string()),
+    };
+    if name.is_empty() {
+        return Err(TOTPError::new("the label has no account name"))
+    }
+
+    let mut issuer = label_issuer.filter(|issuer| !issuer.is_empty());
+    let mut secret = None;
+    let mut counter = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = percent_decode(value)?;
+        match key.to_ascii_lowercase().as_str() {
+            "secret" => {
+                // secrets are often shown in groups with spaces and can have a padding
+                let normalized: String = value.chars().filter(|c| !c.is_whitespace() && *c != '=')
+                    .collect::<String>().to_ascii_uppercase();
+                if normalized.is_empty() || base32::decode(Alphabet::Rfc4648 { padding: false }, &normalized).is_none() {
+                    return Err(TOTPError::new(format!("the secret '{value}' isn't a valid base32 value")))
+                }
+                secret = Some(normalized)
+            }
+            // the parameter is preferred as it isn't ambiguous
+            "issuer" if !value.is_empty() => issuer = Some(value),
+            "issuer" => (),
+            "algorithm" => account.algorithm = Algorithm::parse(&value)
+                .ok_or_else(|| TOTPError::new(format!("unsupported algorithm '{value}', it can be SHA1, SHA256 or SHA512")))?,
+            "digits" => account.digits = value.parse().ok().filter(|digits| (MIN_DIGITS..=MAX_DIGITS).contains(digits))
+                .ok_or_else(|| TOTPError::new(format!("the digits value '{value}' isn't a number from {MIN_DIGITS} to {MAX_DIGITS}")))?,
+            "period" => account.period = value.parse().ok().filter(|&period| period > 0)
+                .ok_or_else(|| TOTPError::new(format!("the period '{value}' isn't a positive number of seconds")))?,
+            "counter" => counter = Some(value.parse::<u64>()
+                .map_err(|_| TOTPError::new(format!("the counter '{value}' isn't a valid number")))?),
+            "encoder" if value.eq_ignore_ascii_case("steam") => account.encoding = Encoding::Steam,
+            // unknown parameters, like an image, are ignored
+            _ => (),
+        }
+    }
+    account.secret = secret.ok_or_else(|| TOTPError::new("the URI has no secret parameter"))?;
+    if account.counter.is_some() {
+        account.counter = Some(counter.ok_or_else(|| TOTPError::new("the counter parameter is required for hotp"))?)
+    }
+    account.issuer = issuer.clone();
+    Ok(OtpAuth { issuer, name, account })
+}
+
+/// Decodes %XX sequences of the URI component.
+pub fn percent_decode(value: &str) -> Result<String, TOTPError> {
+    let bytes = value.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| TOTPError::new(format!("invalid percent encoding at {i} in '{value}'")))?;
+            res.push(hex);
+            i += 3;
+        } else {
+            res.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(res).map_err(|_| TOTPError::new(format!("'{value}' isn't UTF-8 when decoded")))
+}
+
+/// Makes a Key URI of the account, the namespace is the issuer when the account has no own.
+/// T0 isn't a part of the format and doesn't get exported.
+pub fn build(namespace: &str, name: &str, account: &Account) -> String {
+    let issuer = account.issuer.as_deref().unwrap_or(namespace);
+    let kind = if account.counter.is_some() { "hotp" } else { "totp" };
+    let mut res = format!("{SCHEME}{kind}/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+        percent_encode(issuer), percent_encode(name), account.secret, percent_encode(issuer),
+        account.algorithm.name(), account.digits, account.period);
+    if let Some(counter) = account.counter {
+        res.push_str(&format!("&counter={counter}"))
+    }
+    if account.encoding == Encoding::Steam {
+        res.push_str("&encoder=steam")
+    }
+    res
+}
+
+/// Encodes all characters except unreserved ones as %XX.
+fn percent_encode(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => res.push(b as char),
+            _ => res.push_str(&format!("%{b:02X}")),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_uri() {
+        let otp = parse("otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60").unwrap();
+        assert_eq!((otp.issuer.as_deref(), otp.name.as_str()), (Some("ACME Co"), "john.doe@email.com"));
+        let account = otp.account;
+        assert_eq!(account.secret, "HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ");
+        assert_eq!((account.algorithm, account.digits, account.period, account.counter), (Algorithm::Sha256, 8, 60, None));
+        // the parameter issuer wins, a secret is normalized
+        let otp = parse(" OTPAUTH://hotp/Old%3Ajohn?secret=jbsw%20y3dp%20ehpk%203pxp%3D%3D&issuer=New&counter=5&encoder=steam ").unwrap();
+        assert_eq!((otp.issuer.as_deref(), otp.name.as_str()), (Some("New"), "john"));
+        assert_eq!((otp.account.secret.as_str(), otp.account.counter), ("JBSWY3DPEHPK3PXP", Some(5)));
+        assert_eq!(otp.account.encoding, Encoding::Steam);
+    }
+
+    #[test]
+    fn invalid_uris() {
+        for (uri, cause) in [
+            ("http://totp/john?secret=JBSWY3DPEHPK3PXP", "the URI has to start with otpauth://"),
+            ("otpauth:/éotp/john?secret=JBSWY3DPEHPK3PXP", "the URI has to start with otpauth://"),
+            ("otpauth://totp/john", "the URI has no parameters"),
+            ("otpauth://yotp/john?secret=JBSWY3DPEHPK3PXP", "unsupported OTP type 'yotp', it can be totp or hotp"),
+            ("otpauth://totp/ACME:?secret=JBSWY3DPEHPK3PXP", "the label has no account name"),
+            ("otpauth://totp/john?secret=JBSWY3DPEHPK3PX1", "the secret 'JBSWY3DPEHPK3PX1' isn't a valid base32 value"),
+            ("otpauth://totp/john?issuer=ACME", "the URI has no secret parameter"),
+            ("otpauth://totp/john?secret=JBSWY3DPEHPK3PXP&algorithm=MD5", "unsupported algorithm 'MD5', it can be SHA1, SHA256 or SHA512"),
+            ("otpauth://totp/john?secret=JBSWY3DPEHPK3PXP&digits=4", "the digits value '4' isn't a number from 6 to 10"),
+            ("otpauth://totp/john?secret=JBSWY3DPEHPK3PXP&period=0", "the period '0' isn't a positive number of seconds"),
+            ("otpauth://hotp/john?secret=JBSWY3DPEHPK3PXP", "the counter parameter is required for hotp"),
+            ("otpauth://totp/jo%2hn?secret=JBSWY3DPEHPK3PXP", "invalid percent encoding at 2 in 'jo%2hn'"),
+        ] {
+            assert_eq!(parse(uri).err().map(|err| err.cause).as_deref(), Some(cause), "{uri}");
+        }
+    }
+
+    #[test]
+    fn build_round_trip() {
+        let account = Account { counter: Some(3), digits: 8, algorithm: Algorithm::Sha512, ..Account::new("JBSWY3DPEHPK3PXP".to_string()) };
+        let uri = build("Work", "john doe", &account);
+        assert_eq!(uri, "otpauth://hotp/Work:john%20doe?secret=JBSWY3DPEHPK3PXP&issuer=Work&algorithm=SHA512&digits=8&period=30&counter=3");
+        let otp = parse(&uri).unwrap();
+        assert_eq!((otp.issuer.as_deref(), otp.name.as_str()), (Some("Work"), "john doe"));
+        assert_eq!(otp.account.to_json(), Account { issuer: Some("Work".to_string()), ..account }.to_json());
+    }
+}
@@ -0,0 +1,118 @@
+/*
+State file of Google Authenticator PAM module (~/.google_authenticator)
+
+    JBSWY3DPEHPK3PXP            // base32 secret
+    " RATE_LIMIT 3 30           // option lines start with a quote
+    " WINDOW_SIZE 17
+    " DISALLOW_REUSE
+    " TOTP_AUTH                 // or " HOTP_COUNTER 1 for HOTP
+    " STEP_SIZE 30              // the period, 30 seconds when absent
+    12345678                    // scratch codes, 8 digits
+    23456789
+
+The module generates codes of 6 digits by HMAC-SHA1, the time step can be up to 60 seconds.
+The file has to be readable only by its owner.
+*/
+use crate::{TOTPError, account::{Account, Algorithm, Encoding, DEFAULT_PERIOD}};
+
+const DIGITS: u32 = 6;
+const MAX_STEP_SIZE: u64 = 60;
+const SCRATCH_CODE_LEN: usize = 8;
+
+/// Reads the file as a TOTP account, unless the HOTP counter option is present, options which
+/// don't describe codes are ignored.
+pub fn parse(text: &str) -> Result<Account, TOTPError> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim())).filter(|(_, line)| !line.is_empty());
+    let (_, secret) = lines.next().ok_or_else(|| TOTPError::new("the file is empty"))?;
+    let secret = secret.to_uppercase();
+    if base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &secret).is_none() {
+        return Err(TOTPError::new("the secret of the first line isn't valid base32"))
+    }
+    let mut res = Account::new(secret);
+    for (line_no, line) in lines {
+        if let Some(option) = line.strip_prefix('"') {
+            let mut words = option.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let value = words.next().and_then(|value| value.parse::<u64>().ok());
+            let invalid = || TOTPError::new(format!("{name} option has no valid value at line {line_no}"));
+            match name {
+                "HOTP_COUNTER" => res.counter = Some(value.ok_or_else(invalid)?),
+                "STEP_SIZE" => res.period = value.filter(|step| (1..=MAX_STEP_SIZE).contains(step))
+                    .ok_or_else(|| TOTPError::new(format!("STEP_SIZE has to be from 1 to {MAX_STEP_SIZE} at line {line_no}")))?,
+                _ => (),
+            }
+        } else if line.len() == SCRATCH_CODE_LEN && line.bytes().all(|b| b.is_ascii_digit()) {
+            res.scratch_codes.push(line.to_string())
+        } else {
+            return Err(TOTPError::new(format!("invalid scratch code at line {line_no}")))
+        }
+    }
+    Ok(res)
+}
+
+/// Makes the file of the account, the error tells why the module can't use the account.
+pub fn build(account: &Account) -> Result<String, TOTPError> {
+    if account.algorithm != Algorithm::Sha1 {
+        return Err(TOTPError::new(format!("{} algorithm isn't supported, only SHA1", account.algorithm.name())))
+    }
+    if account.digits != DIGITS || account.encoding != Encoding::Decimal {
+        return Err(TOTPError::new(format!("only codes of {DIGITS} digits are supported")))
+    }
+    if account.t0 != 0 {
+        return Err(TOTPError::new("T0 isn't supported"))
+    }
+    let mut res = format!("{}\n", account.secret);
+    match account.counter {
+        Some(counter) => res.push_str(&format!("\" HOTP_COUNTER {counter}\n")),
+        None => {
+            if account.period > MAX_STEP_SIZE {
+                return Err(TOTPError::new(format!("the period can be up to {MAX_STEP_SIZE} seconds")))
+            }
+            if account.period != DEFAULT_PERIOD {
+                res.push_str(&format!("\" STEP_SIZE {}\n", account.period))
+            }
+            res.push_str("\" DISALLOW_REUSE\n\" TOTP_AUTH\n")
+        }
+    }
+    for code in &account.scratch_codes {
+        res.push_str(code);
+        res.push('\n')
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "jbswy3dpehpk3pxp\n\" RATE_LIMIT 3 30\n\" WINDOW_SIZE 17\n\" DISALLOW_REUSE\n\" STEP_SIZE 60\n\" TOTP_AUTH\n12345678\n\n23456789\n";
+
+    #[test]
+    fn round_trip() {
+        let account = parse(FILE).unwrap();
+        assert_eq!((account.secret.as_str(), account.period, account.counter), ("JBSWY3DPEHPK3PXP", 60, None));
+        assert_eq!(account.scratch_codes, ["12345678", "23456789"]);
+        let file = build(&account).unwrap();
+        assert_eq!(file, "JBSWY3DPEHPK3PXP\n\" STEP_SIZE 60\n\" DISALLOW_REUSE\n\" TOTP_AUTH\n12345678\n23456789\n");
+        assert_eq!(parse(&file).unwrap().scratch_codes, account.scratch_codes);
+        let hotp = parse("JBSWY3DPEHPK3PXP\n\" HOTP_COUNTER 4\n").unwrap();
+        assert_eq!(build(&hotp).unwrap(), "JBSWY3DPEHPK3PXP\n\" HOTP_COUNTER 4\n");
+        assert_eq!((hotp.counter, hotp.period), (Some(4), DEFAULT_PERIOD));
+    }
+
+    #[test]
+    fn invalid() {
+        for (file, cause) in [("", "the file is empty"), ("JBSW1\n", "the secret of the first line isn't valid base32"),
+            ("JBSWY3DP\n\" HOTP_COUNTER\n", "HOTP_COUNTER option has no valid value at line 2"),
+            ("JBSWY3DP\n\" STEP_SIZE 90\n", "STEP_SIZE has to be from 1 to 60 at line 2"), ("JBSWY3DP\n\n1234\n", "invalid scratch code at line 3")] {
+            assert_eq!(parse(file).err().unwrap().cause, cause);
+        }
+        let account = Account::new("JBSWY3DPEHPK3PXP".to_string());
+        for (account, cause) in [(Account { algorithm: Algorithm::Sha256, ..account.clone() }, "SHA256 algorithm isn't supported, only SHA1"),
+            (Account { digits: 8, ..account.clone() }, "only codes of 6 digits are supported"),
+            (Account { encoding: Encoding::Steam, ..account.clone() }, "only codes of 6 digits are supported"),
+            (Account { t0: 1, ..account.clone() }, "T0 isn't supported"), (Account { period: 90, ..account }, "the period can be up to 60 seconds")] {
+            assert_eq!(build(&account).err().unwrap().cause, cause);
+        }
+    }
+}
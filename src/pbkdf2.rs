@@ -0,0 +1,54 @@
+/*
+PBKDF2 as described in RFC 8018
+
+    DK = T_1 || T_2 || ... || T_dklen/hlen
+    T_i = F(Password, Salt, c, i)
+    F(Password, Salt, c, i) = U_1 ^ U_2 ^ ... ^ U_c
+    U_1 = PRF(Password, Salt || INT_32_BE(i))
+    U_j = PRF(Password, U_{j-1})
+*/
+use crate::{hmac::{Digest, Hmac}, Sha1};
+
+/// Derives a key of the `out` length from the password using HMAC-SHA1 as the PRF.
+pub fn pbkdf2_hmac_sha1(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    pbkdf2_hmac::<Sha1>(password, salt, iterations, out)
+}
+
+/// Derives a key of the `out` length from the password using HMAC of the hash function as the PRF.
+pub fn pbkdf2_hmac<D: Digest>(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    let prf = Hmac::<D>::new(password);
+    for (i, chunk) in out.chunks_mut(D::OUTPUT_LEN).enumerate() {
+        let mut msg = salt.to_vec();
+        msg.extend_from_slice(&(i as u32 + 1).to_be_bytes());
+        let mut u = prf.mac(&msg);
+        let mut t = u.as_ref().to_vec();
+        for _ in 1..iterations {
+            u = prf.mac(u.as_ref());
+            for (t, u) in t.iter_mut().zip(u.as_ref()) {
+                *t ^= u
+            }
+        }
+        chunk.copy_from_slice(&t[..chunk.len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6070 test vectors
+    #[test]
+    fn hmac_sha1() {
+        let derive = |password: &[u8], salt: &[u8], iterations, len| {
+            let mut key = vec![0u8; len];
+            pbkdf2_hmac_sha1(password, salt, iterations, &mut key);
+            simweb::to_hex(&key)
+        };
+        assert_eq!(derive(b"password", b"salt", 1, 20), "0c60c80f961f0e71f3a9b524af6012062fe037a6");
+        assert_eq!(derive(b"password", b"salt", 2, 20), "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957");
+        assert_eq!(derive(b"password", b"salt", 4096, 20), "4b007901b765489abead49d926f721d065a429c1");
+        assert_eq!(derive(b"passwordPASSWORDpassword", b"saltSALTsaltSALTsaltSALTsaltSALTsalt", 4096, 25),
+            "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038");
+        assert_eq!(derive(b"pass\0word", b"sa\0lt", 4096, 16), "56fa6aa75548099dcc37d7f03425e0c3");
+    }
+}
@@ -0,0 +1,291 @@
+/*
+PNG decoding to gray levels as described in the PNG specification
+
+    signature:  89 50 4E 47 0D 0A 1A 0A
+    chunk:      length 4 bytes big-endian | type 4 bytes | data | CRC 4 bytes
+    IHDR:       width 4 | height 4 | bit depth 1 | color type 1 | compression 1 | filter 1 | interlace 1
+    PLTE:       RGB triples
+    tRNS:       alpha of palette entries
+    IDAT:       zlib compressed scanlines, every scanline starts with a filter type byte
+    IEND
+
+The integrity is checked by zlib Adler-32, so chunk CRCs aren't verified.
+*/
+use crate::{TOTPError, inflate};
+use std::convert::TryInto;
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+/// a larger image isn't expected to be a code screenshot
+const MAX_PIXELS: usize = 64 * 1024 * 1024;
+/// Adam7 passes as (x start, y start, x step, y step)
+const ADAM7: [(usize, usize, usize, usize); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4),
+    (1, 0, 2, 2), (0, 1, 1, 2)];
+
+/// An image as gray levels of pixels, 0 is black.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub luma: Vec<u8>,
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color: u8,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+
+    /// bytes of a pixel used by filters, at least one
+    fn filter_bpp(&self) -> usize {
+        (self.channels() * self.depth as usize).div_ceil(8)
+    }
+
+    fn stride(&self, width: usize) -> usize {
+        (width * self.channels() * self.depth as usize).div_ceil(8)
+    }
+}
+
+/// Decodes an image of any color type and bit depth, transparent pixels are on white.
+pub fn decode(data: &[u8]) -> Result<Image, TOTPError> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(TOTPError::new("not a PNG image"))
+    }
+    let mut pos = SIGNATURE.len();
+    let mut header = None;
+    let mut interlaced = false;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+    loop {
+        let len = data.get(pos..pos + 4).map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize)
+            .ok_or_else(|| TOTPError::new("truncated PNG chunk"))?;
+        let kind = &data.get(pos + 4..pos + 8).ok_or_else(|| TOTPError::new("truncated PNG chunk"))?;
+        let chunk = data.get(pos + 8..pos + 8 + len).ok_or_else(|| TOTPError::new("truncated PNG chunk"))?;
+        pos += 12 + len;
+        match *kind {
+            b"IHDR" => {
+                if chunk.len() != 13 {
+                    return Err(TOTPError::new("invalid PNG header"))
+                }
+                let be = |i: usize| u32::from_be_bytes(chunk[i..i + 4].try_into().unwrap()) as usize;
+                let res = Header { width: be(0), height: be(4), depth: chunk[8], color: chunk[9] };
+                let valid_depth = match res.color {
+                    0 => [1, 2, 4, 8, 16].contains(&res.depth),
+                    3 => [1, 2, 4, 8].contains(&res.depth),
+                    2 | 4 | 6 => [8, 16].contains(&res.depth),
+                    _ => false,
+                };
+                if !valid_depth {
+                    return Err(TOTPError::new(format!("unsupported PNG color type {} with bit depth {}", res.color, res.depth)))
+                }
+                if res.width == 0 || res.height == 0 || res.width.saturating_mul(res.height) > MAX_PIXELS {
+                    return Err(TOTPError::new(format!("PNG image size {}x{} isn't supported", res.width, res.height)))
+                }
+                if chunk[10] != 0 || chunk[11] != 0 {
+                    return Err(TOTPError::new("unknown PNG compression or filter method"))
+                }
+                interlaced = chunk[12] == 1;
+                header = Some(res)
+            }
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            // ancillary chunks don't matter for gray levels
+            _ => (),
+        }
+    }
+    let header = header.ok_or_else(|| TOTPError::new("no PNG header"))?;
+    if header.color == 3 && palette.is_empty() {
+        return Err(TOTPError::new("no PNG palette"))
+    }
+    let passes: Vec<_> = if interlaced { ADAM7.to_vec() } else { vec![(0, 0, 1, 1)] };
+    // every pass has its own scanlines with filter bytes
+    let pass_sizes: Vec<_> = passes.iter().map(|&(x0, y0, dx, dy)|
+        (header.width.saturating_sub(x0).div_ceil(dx), header.height.saturating_sub(y0).div_ceil(dy))).collect();
+    let expected: usize = pass_sizes.iter().filter(|&&(w, h)| w > 0 && h > 0)
+        .map(|&(w, h)| (header.stride(w) + 1) * h).sum();
+    let raw = inflate::zlib_decompress(&compressed, expected)?;
+    if raw.len() < expected {
+        return Err(TOTPError::new("truncated PNG image data"))
+    }
+
+    let mut luma = vec![255u8; header.width * header.height];
+    let mut offset = 0;
+    for (&(x0, y0, dx, dy), &(width, height)) in passes.iter().zip(&pass_sizes) {
+        if width == 0 || height == 0 {
+            continue
+        }
+        let stride = header.stride(width);
+        let bpp = header.filter_bpp();
+        let mut prev = vec![0u8; stride];
+        for row in 0..height {
+            let filter = raw[offset];
+            let mut line = raw[offset + 1..offset + 1 + stride].to_vec();
+            offset += stride + 1;
+            unfilter(filter, &mut line, &prev, bpp)?;
+            for col in 0..width {
+                luma[(y0 + row * dy) * header.width + x0 + col * dx] = pixel_luma(&header, &line, col, palette, transparency)
+            }
+            prev = line
+        }
+    }
+    Ok(Image { width: header.width, height: header.height, luma })
+}
+
+fn unfilter(filter: u8, line: &mut [u8], prev: &[u8], bpp: usize) -> Result<(), TOTPError> {
+    for i in 0..line.len() {
+        let left = if i >= bpp { line[i - bpp] } else { 0 };
+        let up = prev[i];
+        let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+        line[i] = line[i].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(TOTPError::new(format!("unknown PNG filter type {filter}"))),
+        })
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+/// Gray level of the pixel composed on white.
+fn pixel_luma(header: &Header, line: &[u8], col: usize, palette: &[u8], transparency: &[u8]) -> u8 {
+    let depth = header.depth as usize;
+    // a sample scaled to 8 bits, 16 bits samples are reduced to the high byte
+    let sample = |channel: usize| -> u8 {
+        let index = col * header.channels() + channel;
+        match depth {
+            8 => line[index],
+            16 => line[index * 2],
+            _ => {
+                let bit = index * depth;
+                let value = (line[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1);
+                if header.color == 3 { value } else { (value as u16 * 255 / ((1 << depth) - 1)) as u8 }
+            }
+        }
+    };
+    let gray = |r: u8, g: u8, b: u8| ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8;
+    let (luma, alpha) = match header.color {
+        0 => (sample(0), 255),
+        2 => (gray(sample(0), sample(1), sample(2)), 255),
+        3 => {
+            let index = sample(0) as usize;
+            let rgb = palette.get(index * 3..index * 3 + 3).unwrap_or(&[0, 0, 0]);
+            (gray(rgb[0], rgb[1], rgb[2]), *transparency.get(index).unwrap_or(&255))
+        }
+        4 => (sample(0), sample(1)),
+        _ => (gray(sample(0), sample(1), sample(2)), sample(3)),
+    };
+    ((luma as u32 * alpha as u32 + 255 * (255 - alpha as u32)) / 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{qrcode::QrCode, qrdecode};
+
+    /// A PNG image of the scanlines stored in zlib data without compression.
+    fn png(width: u32, height: u32, depth: u8, color: u8, scanlines: &[u8], extra: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        fn crc32(data: &[u8]) -> u32 {
+            !data.iter().fold(!0u32, |crc, &b| (0..8).fold(crc ^ b as u32, |crc, _| (crc >> 1) ^ (0xEDB88320 & (crc & 1).wrapping_neg())))
+        }
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<_> = scanlines.chunks(u16::MAX as usize).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            zlib.push((i == blocks.len() - 1) as u8);
+            zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            zlib.extend_from_slice(block)
+        }
+        let (a, b) = scanlines.iter().fold((1u32, 0u32), |(a, b), &byte| ((a + byte as u32) % 65521, (b + a + byte as u32) % 65521));
+        zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[depth, color, 0, 0, 0]);
+        let mut res = SIGNATURE.to_vec();
+        let mut chunks = vec![(b"IHDR", &header[..])];
+        chunks.extend_from_slice(extra);
+        chunks.extend_from_slice(&[(b"IDAT", &zlib[..]), (b"IEND", &[])]);
+        for (kind, data) in chunks {
+            res.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let start = res.len();
+            res.extend_from_slice(kind);
+            res.extend_from_slice(data);
+            let crc = crc32(&res[start..]);
+            res.extend_from_slice(&crc.to_be_bytes())
+        }
+        res
+    }
+
+    #[test]
+    fn filters() {
+        // rows of 10 20 30, 40 50 60, 70 80 90 and 100 95 90 filtered by Sub, Up, Average and Paeth
+        let scanlines = [1, 10, 10, 10, 2, 30, 30, 30, 3, 50, 20, 20, 4, 30, 251, 251];
+        let image = decode(&png(3, 4, 8, 0, &scanlines, &[])).unwrap();
+        assert_eq!((image.width, image.height), (3, 4));
+        assert_eq!(image.luma, [10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 95, 90]);
+        assert_eq!(decode(&png(3, 1, 8, 0, &[5, 0, 0, 0], &[])).err().unwrap().cause, "unknown PNG filter type 5");
+    }
+
+    #[test]
+    fn color_types() {
+        // red, green and blue
+        let image = decode(&png(3, 1, 8, 2, &[0, 255, 0, 0, 0, 255, 0, 0, 0, 255], &[])).unwrap();
+        assert_eq!(image.luma, [76, 149, 29]);
+        // a black and white palette, the second entry is transparent
+        let palette: &[(&[u8; 4], &[u8])] = &[(b"PLTE", &[0, 0, 0, 255, 255, 255]), (b"tRNS", &[255, 0])];
+        let image = decode(&png(9, 1, 1, 3, &[0, 0b1010_0000, 0b1000_0000], palette)).unwrap();
+        assert_eq!(image.luma, [255, 0, 255, 0, 0, 0, 0, 0, 255]);
+        // 16 bits gray with alpha, half transparent black
+        let image = decode(&png(1, 1, 16, 4, &[0, 0, 0, 0x80, 0], &[])).unwrap();
+        assert_eq!(image.luma, [127]);
+        assert_eq!(decode(&png(1, 1, 4, 2, &[0, 0], &[])).err().unwrap().cause, "unsupported PNG color type 2 with bit depth 4");
+    }
+
+    #[test]
+    fn malformed() {
+        let data = png(2, 2, 8, 0, &[0, 0, 255, 0, 255, 0], &[]);
+        assert!(decode(&data).is_ok());
+        for len in 0..data.len() - 12 {
+            assert!(decode(&data[..len]).is_err(), "truncated to {len}");
+        }
+        assert_eq!(decode(&data[1..]).err().unwrap().cause, "not a PNG image");
+        assert_eq!(decode(&png(2, 2, 8, 0, &[0, 0, 255, 0, 255], &[])).err().unwrap().cause, "truncated PNG image data");
+        assert!(decode(&png(0, 2, 8, 0, &[0], &[])).is_err());
+        assert!(decode(&png(1 << 16, 1 << 16, 8, 0, &[0], &[])).is_err());
+        assert_eq!(decode(&png(2, 1, 8, 3, &[0, 0, 0], &[])).err().unwrap().cause, "no PNG palette");
+    }
+
+    #[test]
+    fn qr_code_round_trip() {
+        let uri = "otpauth://totp/simtotp:john@example.com?secret=JBSWY3DPEHPK3PXP&issuer=simtotp";
+        let code = QrCode::encode(uri.as_bytes()).unwrap();
+        // 3 pixels a module and 4 modules of the quiet zone
+        let width = (code.size + 8) * 3;
+        let mut scanlines = Vec::new();
+        for y in 0..width {
+            scanlines.push(0);
+            scanlines.extend((0..width).map(|x| if code.dark(x as isize / 3 - 4, y as isize / 3 - 4) { 0 } else { 255 }))
+        }
+        let image = decode(&png(width as u32, width as u32, 8, 0, &scanlines, &[])).unwrap();
+        assert_eq!(qrdecode::decode(&image).unwrap(), uri);
+    }
+}
@@ -0,0 +1,586 @@
+/*
+QR code Model 2 encoder as described in ISO/IEC 18004
+
+    data is encoded in the byte mode with the medium error correction level, version 1 to 40
+    is selected by the data length, the tables cover all levels for decoding
+
+    bits:      mode 0100 | character count (8 bits till version 9, 16 bits after) | data
+               | terminator 0000 | zero bits to a byte boundary | pad bytes EC 11 EC 11 ...
+    codewords: data split in blocks, every block gets Reed-Solomon codewords over GF(256)
+               with the polynomial x^8 + x^4 + x^3 + x^2 + 1, and then blocks are interleaved
+    matrix:    finder, timing and alignment patterns, format and version information,
+               codewords placed by two columns zigzag from the bottom right corner,
+               the data mask producing the lowest penalty
+*/
+
+/// Error correction codewords per block, indexed by a level and a version.
+const ECC_CODEWORDS_PER_BLOCK: [[usize; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28,
+        28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26,
+        26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30,
+        28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28,
+        30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+/// Number of blocks, indexed by a level and a version.
+const NUM_ERROR_CORRECTION_BLOCKS: [[usize; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8,
+        8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16,
+        17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20,
+        23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25,
+        25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+pub const MIN_VERSION: usize = 1;
+pub const MAX_VERSION: usize = 40;
+
+/// Error correction level, the order is the one of the tables.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ecc {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+impl Ecc {
+    /// Bits of the level in format information.
+    fn format_bits(self) -> u32 {
+        match self {
+            Ecc::Low => 1,
+            Ecc::Medium => 0,
+            Ecc::Quartile => 3,
+            Ecc::High => 2,
+        }
+    }
+
+    pub fn from_format_bits(bits: u32) -> Self {
+        match bits & 3 {
+            1 => Ecc::Low,
+            0 => Ecc::Medium,
+            3 => Ecc::Quartile,
+            _ => Ecc::High,
+        }
+    }
+}
+/// light modules around a code required by scanners
+const QUIET_ZONE: usize = 4;
+
+/// A square matrix of dark and light modules.
+pub struct QrCode {
+    pub size: usize,
+    modules: Vec<bool>,
+    /// modules of patterns which aren't data and don't get masked
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encodes the data in the smallest version fitting it, `None` is returned when the data
+    /// are too long for any version.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (MIN_VERSION..=MAX_VERSION).find(|&version| {
+            let count_bits = count_bits(version);
+            data.len() < 1 << count_bits && 4 + count_bits + data.len() * 8 <= data_codewords(version, Ecc::Medium) * 8
+        })?;
+        let capacity = data_codewords(version, Ecc::Medium) * 8;
+
+        let mut bits = BitBuffer::default();
+        bits.append(0b0100, 4);
+        bits.append(data.len() as u32, count_bits(version));
+        for &b in data {
+            bits.append(b as u32, 8)
+        }
+        bits.append(0, (capacity - bits.len).min(4));
+        bits.append(0, (8 - bits.len % 8) % 8);
+        for pad in [0xEC, 0x11].into_iter().cycle() {
+            if bits.len >= capacity {
+                break
+            }
+            bits.append(pad, 8)
+        }
+
+        let mut res = QrCode::new(version);
+        res.draw_codewords(&add_ecc_and_interleave(&bits.bytes, version, Ecc::Medium));
+        let mut best = (0, usize::MAX);
+        for mask in 0..8 {
+            res.apply_mask(mask);
+            res.draw_format_bits(mask);
+            let penalty = res.penalty();
+            if penalty < best.1 {
+                best = (mask, penalty)
+            }
+            // masking twice restores the modules
+            res.apply_mask(mask);
+        }
+        res.apply_mask(best.0);
+        res.draw_format_bits(best.0);
+        Some(res)
+    }
+
+    /// Tells if the module is dark, modules outside of the code are light.
+    pub fn dark(&self, x: isize, y: isize) -> bool {
+        let size = self.size as isize;
+        (0..size).contains(&x) && (0..size).contains(&y) && self.modules[y as usize * self.size + x as usize]
+    }
+
+    /// SVG image of the code with the quiet zone, a module is a unit of the view box.
+    pub fn to_svg(&self) -> String {
+        let dimension = self.size + QUIET_ZONE * 2;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.modules[y * self.size + x] {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE))
+                }
+            }
+        }
+        format!(r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {dimension} {dimension}" stroke="none"><rect width="100%" height="100%" fill="#FFFFFF"/><path d="{path}" fill="#000000"/></svg>"##)
+    }
+
+    /// Text of the code for a terminal, every character presents two modules vertically.
+    /// Light modules are drawn by blocks, so the code is scannable on a dark terminal background.
+    pub fn to_unicode(&self) -> String {
+        let border = QUIET_ZONE as isize;
+        let size = self.size as isize;
+        let mut res = String::new();
+        for y in (-border..size + border).step_by(2) {
+            for x in -border..size + border {
+                res.push(match (self.dark(x, y), self.dark(x, y + 1)) {
+                    (false, false) => '█',
+                    (false, true) => '▀',
+                    (true, false) => '▄',
+                    (true, true) => ' ',
+                })
+            }
+            res.push('\n')
+        }
+        res
+    }
+
+    /// A code of the version with function patterns drawn.
+    fn new(version: usize) -> Self {
+        let size = version * 4 + 17;
+        let mut res = QrCode { size, modules: vec![false; size * size], function: vec![false; size * size] };
+        for i in 0..size {
+            res.set_function(6, i, i % 2 == 0);
+            res.set_function(i, 6, i % 2 == 0);
+        }
+        res.draw_finder(3, 3);
+        res.draw_finder(size - 4, 3);
+        res.draw_finder(3, size - 4);
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // the corners are taken by finders
+                if (i, j) != (0, 0) && (i, j) != (0, last) && (i, j) != (last, 0) {
+                    res.draw_alignment(x, y)
+                }
+            }
+        }
+        // reserves the format area
+        res.draw_format_bits(0);
+        res.draw_version(version);
+        res
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    /// Draws a finder pattern with the separator around the center.
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4isize..=4 {
+            for dx in -4isize..=4 {
+                let (xx, yy) = (x as isize + dx, y as isize + dy);
+                if (0..self.size as isize).contains(&xx) && (0..self.size as isize).contains(&yy) {
+                    let dist = dx.abs().max(dy.abs());
+                    self.set_function(xx as usize, yy as usize, dist != 2 && dist != 4)
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in -2isize..=2 {
+            for dx in -2isize..=2 {
+                self.set_function((x as isize + dx) as usize, (y as isize + dy) as usize, dx.abs().max(dy.abs()) != 1)
+            }
+        }
+    }
+
+    /// Draws both copies of the error correction level and the mask protected by BCH code.
+    fn draw_format_bits(&mut self, mask: u32) {
+        let bits = format_bits(Ecc::Medium, mask);
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+        let size = self.size;
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i))
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i))
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i))
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i))
+        }
+        // always dark
+        self.set_function(8, size - 8, true)
+    }
+
+    /// Versions 7 and above carry the version in two blocks 6x3.
+    fn draw_version(&mut self, version: usize) {
+        if version < 7 {
+            return
+        }
+        let bits = version_bits(version);
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 != 0;
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark)
+        }
+    }
+
+    fn draw_codewords(&mut self, data: &[u8]) {
+        for (i, (x, y)) in self.data_positions().into_iter().enumerate().take(data.len() * 8) {
+            self.modules[y * self.size + x] = (data[i >> 3] >> (7 - (i & 7))) & 1 != 0
+        }
+    }
+
+    /// Data modules in the order of codeword bits, two columns zigzag from the bottom right corner.
+    fn data_positions(&self) -> Vec<(usize, usize)> {
+        let size = self.size;
+        let mut res = Vec::new();
+        let mut right = size as isize - 1;
+        while right >= 1 {
+            // the vertical timing pattern column is skipped
+            if right == 6 {
+                right = 5
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let y = if upward { size - 1 - vert } else { vert };
+                    if !self.function[y * size + x] {
+                        res.push((x, y))
+                    }
+                }
+            }
+            right -= 2
+        }
+        res
+    }
+
+    /// Inverts data modules selected by the mask pattern.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let i = y * self.size + x;
+                self.modules[i] ^= mask_bit(mask, x, y) && !self.function[i]
+            }
+        }
+    }
+
+    /// Penalty of the rules: runs of the same color, 2x2 blocks, finder like patterns, and
+    /// the dark modules balance.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let at = |x: usize, y: usize| self.modules[y * size + x];
+        let mut res = 0;
+        for horizontal in [true, false] {
+            for a in 0..size {
+                let line: Vec<bool> = (0..size).map(|b| if horizontal { at(b, a) } else { at(a, b) }).collect();
+                let mut run = 1;
+                for b in 1..=size {
+                    if b < size && line[b] == line[b - 1] {
+                        run += 1
+                    } else {
+                        if run >= 5 {
+                            res += run - 2
+                        }
+                        run = 1
+                    }
+                }
+                const FINDER_LIKE: [[bool; 11]; 2] = [
+                    [true, false, true, true, true, false, true, false, false, false, false],
+                    [false, false, false, false, true, false, true, true, true, false, true],
+                ];
+                for window in line.windows(11) {
+                    if FINDER_LIKE.iter().any(|pattern| pattern == window) {
+                        res += 40
+                    }
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = at(x, y);
+                if color == at(x + 1, y) && color == at(x, y + 1) && color == at(x + 1, y + 1) {
+                    res += 3
+                }
+            }
+        }
+        let total = size * size;
+        let dark = self.modules.iter().filter(|&&dark| dark).count();
+        // every 5% of deviation from the half
+        let k = (dark * 20).abs_diff(total * 10).div_ceil(total) - 1;
+        res + k * 10
+    }
+}
+
+/// Positions of data modules of the version in the order of codeword bits.
+pub fn data_positions(version: usize) -> Vec<(usize, usize)> {
+    QrCode::new(version).data_positions()
+}
+
+/// Tells if the mask pattern inverts the module.
+pub fn mask_bit(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// 15 bits of the level and the mask protected by BCH code, as they're drawn.
+pub fn format_bits(ecc: Ecc, mask: u32) -> u32 {
+    let data = ecc.format_bits() << 3 | mask;
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537)
+    }
+    (data << 10 | rem) ^ 0x5412
+}
+
+/// 18 bits of the version protected by Golay code.
+pub fn version_bits(version: usize) -> u32 {
+    let mut rem = version as u32;
+    for _ in 0..12 {
+        rem = (rem << 1) ^ ((rem >> 11) * 0x1F25)
+    }
+    (version as u32) << 12 | rem
+}
+
+#[derive(Default)]
+struct BitBuffer {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitBuffer {
+    /// Appends the lowest bits of the value starting from the most significant.
+    fn append(&mut self, value: u32, bits: usize) {
+        for i in (0..bits).rev() {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0)
+            }
+            if (value >> i) & 1 != 0 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.len % 8)
+            }
+            self.len += 1
+        }
+    }
+}
+
+fn count_bits(version: usize) -> usize {
+    if version < 10 { 8 } else { 16 }
+}
+
+/// Modules available for codewords after function patterns.
+pub fn raw_data_modules(version: usize) -> usize {
+    let mut res = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let num_align = version / 7 + 2;
+        res -= (25 * num_align - 10) * num_align - 55;
+        if version >= 7 {
+            res -= 36
+        }
+    }
+    res
+}
+
+fn data_codewords(version: usize, ecc: Ecc) -> usize {
+    let (num_blocks, ecc_len) = block_layout(version, ecc);
+    raw_data_modules(version) / 8 - ecc_len * num_blocks
+}
+
+/// Number of blocks and error correction codewords per block.
+pub fn block_layout(version: usize, ecc: Ecc) -> (usize, usize) {
+    (NUM_ERROR_CORRECTION_BLOCKS[ecc as usize][version], ECC_CODEWORDS_PER_BLOCK[ecc as usize][version])
+}
+
+/// Centers of alignment patterns, the same for rows and columns.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return vec![]
+    }
+    let num_align = version / 7 + 2;
+    let step = (version * 8 + num_align * 3 + 5) / (num_align * 4 - 4) * 2;
+    let size = version * 4 + 17;
+    let mut res: Vec<usize> = (0..num_align - 1).map(|i| size - 7 - i * step).collect();
+    res.push(6);
+    res.reverse();
+    res
+}
+
+/// Splits the data in blocks, adds error correction codewords and interleaves the blocks.
+/// Short blocks are followed by long ones having one more data codeword.
+fn add_ecc_and_interleave(data: &[u8], version: usize, ecc: Ecc) -> Vec<u8> {
+    let (num_blocks, ecc_len) = block_layout(version, ecc);
+    let raw_codewords = raw_data_modules(version) / 8;
+    let num_short_blocks = num_blocks - raw_codewords % num_blocks;
+    let short_block_len = raw_codewords / num_blocks;
+
+    let divisor = reed_solomon_divisor(ecc_len);
+    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut k = 0;
+    for i in 0..num_blocks {
+        let data_len = short_block_len - ecc_len + if i < num_short_blocks { 0 } else { 1 };
+        let mut block = data[k..k + data_len].to_vec();
+        k += data_len;
+        let ecc = reed_solomon_remainder(&block, &divisor);
+        if i < num_short_blocks {
+            // a placeholder skipped at interleaving
+            block.push(0)
+        }
+        block.extend_from_slice(&ecc);
+        blocks.push(block)
+    }
+    let mut res = Vec::with_capacity(raw_codewords);
+    for i in 0..short_block_len + 1 {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_block_len - ecc_len || j >= num_short_blocks {
+                res.push(block[i])
+            }
+        }
+    }
+    res
+}
+
+/// Coefficients of the generator polynomial of the degree without the leading term.
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut res = vec![0u8; degree];
+    res[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            res[j] = gf_multiply(res[j], root);
+            if j + 1 < degree {
+                res[j] ^= res[j + 1]
+            }
+        }
+        root = gf_multiply(root, 0x02)
+    }
+    res
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut res = vec![0u8; divisor.len()];
+    for &b in data {
+        let factor = b ^ res.remove(0);
+        res.push(0);
+        for (r, &d) in res.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(d, factor)
+        }
+    }
+    res
+}
+
+/// Multiplication in GF(2^8) modulo 0x11D.
+pub fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z = 0u8;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x1D);
+        z ^= ((y >> i) & 1) * x
+    }
+    z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// byte mode capacities of the medium level from the specification
+    #[test]
+    fn versions() {
+        for (version, capacity) in [(1, 14), (2, 26), (3, 42), (4, 62), (5, 84), (6, 106), (7, 122), (9, 180), (10, 213)] {
+            assert_eq!(QrCode::encode(&vec![b'a'; capacity]).unwrap().size, 17 + 4 * version);
+            assert_eq!(QrCode::encode(&vec![b'a'; capacity + 1]).unwrap().size, 21 + 4 * version);
+        }
+        assert_eq!(QrCode::encode(&[b'a'; 2331]).unwrap().size, 177);
+        assert!(QrCode::encode(&[b'a'; 2332]).is_none());
+    }
+
+    /// format information of the medium level and masks 0 to 7 from the specification
+    const FORMAT_INFORMATION: [u32; 8] = [0x5412, 0x5125, 0x5E7C, 0x5B4B, 0x45F9, 0x40CE, 0x4F97, 0x4AA0];
+
+    #[test]
+    fn format_information() {
+        for data in [&b"otpauth://totp/simtotp:john?secret=JBSWY3DPEHPK3PXP"[..], &[0; 100], &[0xff; 300]] {
+            let code = QrCode::encode(data).unwrap();
+            let size = code.size as isize;
+            let bit = |x, y, i| (code.dark(x, y) as u32) << i;
+            // the copy around the top left finder
+            let first = (0..6).map(|i| bit(8, i, i)).sum::<u32>() + bit(8, 7, 6) + bit(8, 8, 7) + bit(7, 8, 8) +
+                (9..15).map(|i| bit(14 - i, 8, i)).sum::<u32>();
+            // the copy split between the other finders
+            let second = (0..8).map(|i| bit(size - 1 - i, 8, i)).sum::<u32>() + (8..15).map(|i| bit(8, size - 15 + i, i)).sum::<u32>();
+            assert_eq!(first, second);
+            assert!(FORMAT_INFORMATION.contains(&first), "{first:x}");
+            assert!(code.dark(8, size - 8));
+        }
+    }
+
+    /// version information of versions 7, 8 and 40 from the specification
+    #[test]
+    fn version_information() {
+        for (version, capacity, info) in [(7, 122, 0x07C94), (8, 152, 0x085BC), (40, 2331, 0x28C69)] {
+            let code = QrCode::encode(&vec![b'a'; capacity]).unwrap();
+            let size = code.size as isize;
+            let (mut bottom_left, mut top_right) = (0, 0);
+            for i in 0..18 {
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                bottom_left |= (code.dark(b, a) as u32) << i;
+                top_right |= (code.dark(a, b) as u32) << i;
+            }
+            assert_eq!((bottom_left, top_right), (info, info), "version {version}");
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        let code = QrCode::encode(b"otpauth://totp/simtotp:john?secret=JBSWY3DPEHPK3PXP").unwrap();
+        let size = code.size as isize;
+        for (left, top) in [(0, 0), (size - 7, 0), (0, size - 7)] {
+            for y in -1..8 {
+                for x in -1..8 {
+                    // a ring of 7 modules, a light ring and a 3 x 3 square in the middle, with a light separator
+                    let ring = (x - 3i32).abs().max((y - 3i32).abs());
+                    assert_eq!(code.dark(left + x as isize, top + y as isize), ring != 2 && ring != 4, "finder at {left},{top}");
+                }
+            }
+        }
+        for i in 8..size - 8 {
+            assert_eq!(code.dark(i, 6), i % 2 == 0);
+            assert_eq!(code.dark(6, i), i % 2 == 0);
+        }
+    }
+}
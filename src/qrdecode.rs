@@ -0,0 +1,576 @@
+/*
+QR code decoding from an image
+
+    the image is binarized by a threshold between dark and light levels found by Otsu's method
+    finder patterns are located by 1:1:3:1:1 runs of dark and light pixels in rows, and checked
+    in columns
+    three finder patterns give the code orientation, the module size and the version, and then
+    modules are sampled by an affine transform, so an image with a perspective distortion,
+    like a photo taken at an angle, isn't decoded
+    format information gives the error correction level and the mask, codewords get corrected
+    by Reed-Solomon decoding, and then segments are parsed
+*/
+use crate::{TOTPError, png::Image, qrcode::{self, Ecc, MIN_VERSION, MAX_VERSION}};
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+/// candidates to select three finder patterns from
+const MAX_CANDIDATES: usize = 8;
+
+/// Decodes text of a QR code found in the image.
+pub fn decode(image: &Image) -> Result<String, TOTPError> {
+    let bitmap = Bitmap::new(image);
+    let finders = bitmap.find_finders();
+    let (top_left, top_right, bottom_left) = select_finders(&finders).ok_or_else(|| TOTPError::new("no QR code found"))?;
+    let module = (top_left.module + top_right.module + bottom_left.module) / 3.0;
+    let modules_across = (top_left.distance(&top_right) + top_left.distance(&bottom_left)) / 2.0 / module + 7.0;
+    let estimated = (((modules_across - 17.0) / 4.0).round() as isize).clamp(MIN_VERSION as isize, MAX_VERSION as isize) as usize;
+
+    let mut last_err = TOTPError::new("no QR code found");
+    for version in [estimated, estimated + 1, estimated - 1] {
+        if !(MIN_VERSION..=MAX_VERSION).contains(&version) {
+            continue
+        }
+        let mut grid = Grid::sample(&bitmap, &top_left, &top_right, &bottom_left, version);
+        // the version information is more reliable than the estimation
+        if let Some(read_version) = grid.read_version() && read_version != version {
+            grid = Grid::sample(&bitmap, &top_left, &top_right, &bottom_left, read_version)
+        }
+        match grid.decode() {
+            Ok(text) => return Ok(text),
+            Err(e) => last_err = e,
+        }
+    }
+    Err(last_err)
+}
+
+/// Dark pixels of the image.
+struct Bitmap {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+#[derive(Clone, Copy)]
+struct Finder {
+    x: f64,
+    y: f64,
+    module: f64,
+    count: usize,
+}
+
+impl Finder {
+    fn distance(&self, other: &Finder) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+impl Bitmap {
+    fn new(image: &Image) -> Self {
+        let threshold = otsu_threshold(&image.luma);
+        Bitmap { width: image.width, height: image.height, dark: image.luma.iter().map(|&luma| luma <= threshold).collect() }
+    }
+
+    fn get(&self, x: isize, y: isize) -> Option<bool> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None
+        }
+        Some(self.dark[y as usize * self.width + x as usize])
+    }
+
+    fn find_finders(&self) -> Vec<Finder> {
+        let mut res: Vec<Finder> = Vec::new();
+        for y in 0..self.height {
+            // runs of the row as (start, length), starting from a dark one
+            let row = &self.dark[y * self.width..(y + 1) * self.width];
+            let mut runs = Vec::new();
+            let mut start = 0;
+            for x in 1..=self.width {
+                if x == self.width || row[x] != row[start] {
+                    if row[start] || !runs.is_empty() {
+                        runs.push((start, x - start))
+                    }
+                    start = x
+                }
+            }
+            // runs alternate, so dark ones are at even indexes
+            for i in (0..runs.len().saturating_sub(4)).step_by(2) {
+                let lengths = [runs[i].1, runs[i + 1].1, runs[i + 2].1, runs[i + 3].1, runs[i + 4].1];
+                if finder_module(&lengths).is_none() {
+                    continue
+                }
+                let x = runs[i + 2].0 as f64 + runs[i + 2].1 as f64 / 2.0;
+                if let Some((cy, vertical)) = self.cross_check(x, y as f64 + 0.5, false) &&
+                    let Some((cx, horizontal)) = self.cross_check(x, cy, true) {
+                    let module = (vertical + horizontal) / 2.0;
+                    match res.iter_mut().find(|f| (f.x - cx).abs() < module * 3.0 && (f.y - cy).abs() < module * 3.0) {
+                        Some(f) => {
+                            let n = f.count as f64;
+                            f.x = (f.x * n + cx) / (n + 1.0);
+                            f.y = (f.y * n + cy) / (n + 1.0);
+                            f.module = (f.module * n + module) / (n + 1.0);
+                            f.count += 1
+                        }
+                        None => res.push(Finder { x: cx, y: cy, module, count: 1 })
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    /// Checks 1:1:3:1:1 runs through the point in a row or a column, and returns the center
+    /// coordinate along the line and the module size.
+    fn cross_check(&self, x: f64, y: f64, horizontal: bool) -> Option<(f64, f64)> {
+        let (x0, y0) = (x as isize, y as isize);
+        let (dx, dy) = if horizontal { (1, 0) } else { (0, 1) };
+        let run = |from: isize, step: isize, dark: bool| {
+            let mut n = 0;
+            while self.get(x0 + (from + n * step) * dx, y0 + (from + n * step) * dy) == Some(dark) {
+                n += 1
+            }
+            n
+        };
+        let back = run(0, -1, true);
+        let forth = run(1, 1, true);
+        if back == 0 {
+            return None
+        }
+        let light_back = run(-back, -1, false);
+        let dark_back = run(-back - light_back, -1, true);
+        let light_forth = run(1 + forth, 1, false);
+        let dark_forth = run(1 + forth + light_forth, 1, true);
+        let lengths = [dark_back, light_back, back + forth, light_forth, dark_forth].map(|n| n as usize);
+        let module = finder_module(&lengths)?;
+        let base = if horizontal { x0 } else { y0 } as f64;
+        Some((base + (forth + 2 - back) as f64 / 2.0, module))
+    }
+}
+
+/// The module size when the runs are in 1:1:3:1:1 proportion with a half module tolerance.
+fn finder_module(lengths: &[usize; 5]) -> Option<f64> {
+    let total: usize = lengths.iter().sum();
+    if total < 7 || lengths.contains(&0) {
+        return None
+    }
+    let module = total as f64 / 7.0;
+    let tolerance = module / 2.0;
+    lengths.iter().zip([1.0, 1.0, 3.0, 1.0, 1.0]).all(|(&len, ratio)| (len as f64 - module * ratio).abs() < tolerance * ratio)
+        .then_some(module)
+}
+
+/// Selects three patterns making the closest to an isosceles right triangle, and orders them
+/// as top left, top right and bottom left.
+fn select_finders(finders: &[Finder]) -> Option<(Finder, Finder, Finder)> {
+    let mut candidates: Vec<Finder> = finders.to_vec();
+    // a pattern crossed by few rows is likely a noise
+    if candidates.iter().filter(|f| f.count > 1).count() >= 3 {
+        candidates.retain(|f| f.count > 1)
+    }
+    candidates.sort_by_key(|f| std::cmp::Reverse(f.count));
+    candidates.truncate(MAX_CANDIDATES);
+    let mut best: Option<(f64, [Finder; 3])> = None;
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            for k in j + 1..candidates.len() {
+                let mut triple = [candidates[i], candidates[j], candidates[k]];
+                // the right angle is opposite to the longest side
+                let sides = [triple[1].distance(&triple[2]), triple[0].distance(&triple[2]), triple[0].distance(&triple[1])];
+                let corner = (0..3).max_by(|&a, &b| sides[a].total_cmp(&sides[b])).unwrap();
+                triple.swap(0, corner);
+                let (a, b) = (triple[0].distance(&triple[1]), triple[0].distance(&triple[2]));
+                let hypotenuse = triple[1].distance(&triple[2]);
+                let modules = triple.map(|f| f.module);
+                let max_module = modules.iter().cloned().fold(0.0, f64::max);
+                let min_module = modules.iter().cloned().fold(f64::MAX, f64::min);
+                if a.min(b) < max_module * 10.0 {
+                    // finders of a code are 14 modules apart at least, it leaves a margin for the estimation
+                    continue
+                }
+                let score = (a - b).abs() / a.max(b) + (hypotenuse - a.hypot(b)).abs() / hypotenuse
+                    + (max_module - min_module) / max_module;
+                if best.is_none_or(|(best_score, _)| score < best_score) {
+                    best = Some((score, triple))
+                }
+            }
+        }
+    }
+    let [top_left, a, b] = best?.1;
+    // in the image coordinates with y down, top right to bottom left is clockwise
+    let cross = (a.x - top_left.x) * (b.y - top_left.y) - (a.y - top_left.y) * (b.x - top_left.x);
+    Some(if cross > 0.0 { (top_left, a, b) } else { (top_left, b, a) })
+}
+
+fn otsu_threshold(luma: &[u8]) -> u8 {
+    let mut histogram = [0usize; 256];
+    for &l in luma {
+        histogram[l as usize] += 1
+    }
+    let total = luma.len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+    let (mut sum_back, mut weight_back, mut max, mut res) = (0.0, 0.0, 0.0, 127);
+    for (t, &n) in histogram.iter().enumerate() {
+        weight_back += n as f64;
+        if weight_back == 0.0 {
+            continue
+        }
+        let weight_fore = total - weight_back;
+        if weight_fore == 0.0 {
+            break
+        }
+        sum_back += t as f64 * n as f64;
+        let (mean_back, mean_fore) = (sum_back / weight_back, (sum - sum_back) / weight_fore);
+        let between = weight_back * weight_fore * (mean_back - mean_fore).powi(2);
+        if between > max {
+            max = between;
+            res = t as u8
+        }
+    }
+    res
+}
+
+/// Sampled modules of a code.
+struct Grid {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl Grid {
+    /// Samples centers of modules, finder centers are in the middle of modules 3 from the code corners.
+    fn sample(bitmap: &Bitmap, top_left: &Finder, top_right: &Finder, bottom_left: &Finder, version: usize) -> Self {
+        let size = version * 4 + 17;
+        let span = (size - 7) as f64;
+        let (ux, uy) = ((top_right.x - top_left.x) / span, (top_right.y - top_left.y) / span);
+        let (vx, vy) = ((bottom_left.x - top_left.x) / span, (bottom_left.y - top_left.y) / span);
+        let mut modules = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let (mx, my) = (x as f64 - 3.0, y as f64 - 3.0);
+                let px = top_left.x + mx * ux + my * vx;
+                let py = top_left.y + mx * uy + my * vy;
+                modules.push(bitmap.get(px.floor() as isize, py.floor() as isize).unwrap_or(false))
+            }
+        }
+        Grid { size, modules }
+    }
+
+    fn at(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Version from either block of version information, when it's close enough to a valid one.
+    fn read_version(&self) -> Option<usize> {
+        if self.size < 45 {
+            return None
+        }
+        let (mut first, mut second) = (0u32, 0u32);
+        for i in 0..18 {
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            first |= (self.at(a, b) as u32) << i;
+            second |= (self.at(b, a) as u32) << i
+        }
+        (7..=MAX_VERSION).map(|version| {
+            let bits = qrcode::version_bits(version);
+            (version, (bits ^ first).count_ones().min((bits ^ second).count_ones()))
+        }).min_by_key(|&(_, distance)| distance).filter(|&(_, distance)| distance <= 3).map(|(version, _)| version)
+    }
+
+    fn decode(&self) -> Result<String, TOTPError> {
+        let size = self.size;
+        let version = (size - 17) / 4;
+        // both copies of format information, bit positions are as they're drawn
+        let (mut first, mut second) = (0u32, 0u32);
+        for i in 0..15 {
+            let (x, y) = match i {
+                0..=5 => (8, i),
+                6 => (8, 7),
+                7 => (8, 8),
+                8 => (7, 8),
+                _ => (14 - i, 8),
+            };
+            first |= (self.at(x, y) as u32) << i;
+            let (x, y) = if i < 8 { (size - 1 - i, 8) } else { (8, size - 15 + i) };
+            second |= (self.at(x, y) as u32) << i
+        }
+        let (ecc, mask) = (0..32).map(|data| {
+            let (ecc, mask) = (Ecc::from_format_bits(data >> 3), data & 7);
+            let bits = qrcode::format_bits(ecc, mask);
+            ((ecc, mask), (bits ^ first).count_ones().min((bits ^ second).count_ones()))
+        }).min_by_key(|&(_, distance)| distance).filter(|&(_, distance)| distance <= 3)
+            .ok_or_else(|| TOTPError::new("unreadable QR format information"))?.0;
+
+        let raw_codewords = qrcode::raw_data_modules(version) / 8;
+        let mut codewords = vec![0u8; raw_codewords];
+        for (i, (x, y)) in qrcode::data_positions(version).into_iter().enumerate().take(raw_codewords * 8) {
+            if self.at(x, y) ^ qrcode::mask_bit(mask, x, y) {
+                codewords[i >> 3] |= 0x80 >> (i & 7)
+            }
+        }
+
+        // blocks are interleaved, short blocks have no codeword at the position of the last data one of long blocks
+        let (num_blocks, ecc_len) = qrcode::block_layout(version, ecc);
+        let num_short_blocks = num_blocks - raw_codewords % num_blocks;
+        let short_block_len = raw_codewords / num_blocks;
+        let mut blocks = vec![vec![0u8; short_block_len + 1]; num_blocks];
+        let mut next = codewords.into_iter();
+        for i in 0..short_block_len + 1 {
+            for (j, block) in blocks.iter_mut().enumerate() {
+                if i != short_block_len - ecc_len || j >= num_short_blocks {
+                    block[i] = next.next().unwrap_or_default()
+                }
+            }
+        }
+        let gf = GaloisField::new();
+        let mut data = Vec::new();
+        for (j, mut block) in blocks.into_iter().enumerate() {
+            if j < num_short_blocks {
+                block.remove(short_block_len - ecc_len);
+            }
+            gf.correct(&mut block, ecc_len)?;
+            data.extend_from_slice(&block[..block.len() - ecc_len])
+        }
+        parse_segments(&data, version)
+    }
+}
+
+/// GF(2^8) of QR codes with exponent and logarithm tables of the generator 2.
+struct GaloisField {
+    exp: [u8; 510],
+    log: [usize; 256],
+}
+
+impl GaloisField {
+    fn new() -> Self {
+        let mut res = GaloisField { exp: [0; 510], log: [0; 256] };
+        let mut x = 1u8;
+        for i in 0..255 {
+            res.exp[i] = x;
+            res.exp[i + 255] = x;
+            res.log[x as usize] = i;
+            x = qrcode::gf_multiply(x, 2)
+        }
+        res
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 { 0 } else { self.exp[self.log[a as usize] + self.log[b as usize]] }
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 { 0 } else { self.exp[self.log[a as usize] + 255 - self.log[b as usize]] }
+    }
+
+    /// Evaluates the polynomial with coefficients starting from the lowest degree.
+    fn eval(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().rev().fold(0, |acc, &c| self.mul(acc, x) ^ c)
+    }
+
+    /// Corrects up to a half of the error correction length of wrong codewords in the block,
+    /// the block polynomial has roots 2^0 .. 2^(ecc_len-1).
+    fn correct(&self, block: &mut [u8], ecc_len: usize) -> Result<(), TOTPError> {
+        let n = block.len();
+        let syndromes: Vec<u8> = (0..ecc_len)
+            .map(|i| block.iter().fold(0, |acc, &c| self.mul(acc, self.exp[i]) ^ c)).collect();
+        if syndromes.iter().all(|&s| s == 0) {
+            return Ok(())
+        }
+        let uncorrectable = || TOTPError::new("too many errors in the QR code");
+        // Berlekamp-Massey gives the error locator
+        let (mut locator, mut prev) = (vec![1u8], vec![1u8]);
+        let (mut errors, mut shift, mut prev_discrepancy) = (0, 1, 1u8);
+        for k in 0..ecc_len {
+            let mut discrepancy = syndromes[k];
+            for i in 1..=errors.min(locator.len() - 1) {
+                discrepancy ^= self.mul(locator[i], syndromes[k - i])
+            }
+            if discrepancy == 0 {
+                shift += 1;
+                continue
+            }
+            let coef = self.div(discrepancy, prev_discrepancy);
+            let saved = locator.clone();
+            if locator.len() < prev.len() + shift {
+                locator.resize(prev.len() + shift, 0)
+            }
+            for (i, &p) in prev.iter().enumerate() {
+                locator[i + shift] ^= self.mul(coef, p)
+            }
+            if 2 * errors <= k {
+                errors = k + 1 - errors;
+                prev = saved;
+                prev_discrepancy = discrepancy;
+                shift = 1
+            } else {
+                shift += 1
+            }
+        }
+        locator.truncate(errors + 1);
+        if errors * 2 > ecc_len {
+            return Err(uncorrectable())
+        }
+        // the evaluator is syndromes multiplied by the locator modulo x^ecc_len
+        let mut evaluator = vec![0u8; ecc_len];
+        for (i, &l) in locator.iter().enumerate() {
+            for (j, &s) in syndromes.iter().enumerate().take(ecc_len - i) {
+                evaluator[i + j] ^= self.mul(l, s)
+            }
+        }
+        // formal derivative has only odd terms in characteristic 2
+        let derivative: Vec<u8> = locator.iter().enumerate().skip(1).map(|(i, &l)| if i % 2 == 1 { l } else { 0 }).collect();
+        let mut found = 0;
+        for (k, codeword) in block.iter_mut().enumerate() {
+            // the codeword k is the coefficient of x^(n-1-k)
+            let power = n - 1 - k;
+            let x_inv = self.exp[(255 - power % 255) % 255];
+            if self.eval(&locator, x_inv) == 0 {
+                let magnitude = self.mul(self.exp[power % 255], self.div(self.eval(&evaluator, x_inv), self.eval(&derivative, x_inv)));
+                *codeword ^= magnitude;
+                found += 1
+            }
+        }
+        if found != errors {
+            return Err(uncorrectable())
+        }
+        Ok(())
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, bits: usize) -> Result<u32, TOTPError> {
+        if bits > self.remaining() {
+            return Err(TOTPError::new("truncated QR code data"))
+        }
+        let mut res = 0;
+        for _ in 0..bits {
+            res = res << 1 | ((self.data[self.pos >> 3] >> (7 - (self.pos & 7))) & 1) as u32;
+            self.pos += 1
+        }
+        Ok(res)
+    }
+}
+
+/// Parses numeric, alphanumeric and byte segments, ECI designators are skipped as the text is
+/// expected in UTF-8.
+fn parse_segments(data: &[u8], version: usize) -> Result<String, TOTPError> {
+    // character count bits of numeric, alphanumeric and byte modes
+    let count_bits = match version {
+        1..=9 => [10, 9, 8],
+        10..=26 => [12, 11, 16],
+        _ => [14, 13, 16],
+    };
+    let mut bits = BitReader { data, pos: 0 };
+    let mut res = Vec::new();
+    while bits.remaining() >= 4 {
+        match bits.read(4)? {
+            0 => break,
+            1 => {
+                let mut count = bits.read(count_bits[0])? as usize;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = bits.read([0, 4, 7, 10][digits])?;
+                    res.extend_from_slice(format!("{value:0digits$}").as_bytes());
+                    count -= digits
+                }
+            }
+            2 => {
+                let mut count = bits.read(count_bits[1])? as usize;
+                while count > 0 {
+                    let chars = count.min(2);
+                    let value = bits.read(if chars == 2 { 11 } else { 6 })? as usize;
+                    let indexes = if chars == 2 { vec![value / 45, value % 45] } else { vec![value] };
+                    for index in indexes {
+                        res.push(*ALPHANUMERIC.get(index).ok_or_else(|| TOTPError::new("invalid QR alphanumeric character"))?)
+                    }
+                    count -= chars
+                }
+            }
+            4 => {
+                let count = bits.read(count_bits[2])?;
+                for _ in 0..count {
+                    res.push(bits.read(8)? as u8)
+                }
+            }
+            7 => {
+                // the designator is 1, 2 or 3 bytes marked by leading bits
+                let first = bits.read(8)?;
+                if first & 0x80 != 0 {
+                    bits.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            8 => return Err(TOTPError::new("kanji QR segments aren't supported")),
+            mode => return Err(TOTPError::new(format!("unsupported QR segment mode {mode}"))),
+        }
+    }
+    String::from_utf8(res).map_err(|_| TOTPError::new("QR code content isn't text"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qrcode::QrCode;
+
+    /// The code with 4 modules of the quiet zone, a module is `scale` pixels.
+    fn render(text: &str, scale: usize) -> Image {
+        let code = QrCode::encode(text.as_bytes()).unwrap();
+        let width = (code.size + 8) * scale;
+        let luma = (0..width * width).map(|i| {
+            let (x, y) = ((i % width / scale) as isize - 4, (i / width / scale) as isize - 4);
+            if code.dark(x, y) { 0 } else { 255 }
+        }).collect();
+        Image { width, height: width, luma }
+    }
+
+    #[test]
+    fn round_trip() {
+        let uri = "otpauth://totp/simtotp:john?secret=JBSWY3DPEHPK3PXP";
+        // versions 3, 5 and 10, the last has version information
+        for text in [uri.to_string(), format!("{uri}&issuer=simtotp&algorithm=SHA256"), format!("{uri}&image={}", "x".repeat(150))] {
+            for scale in [1, 2, 5] {
+                assert_eq!(decode(&render(&text, scale)).unwrap(), text, "scale {scale}");
+            }
+        }
+    }
+
+    #[test]
+    fn orientation_and_damage() {
+        let text = "otpauth://hotp/simtotp:john?secret=JBSWY3DPEHPK3PXP&counter=7";
+        let mut image = render(text, 3);
+        image.luma.reverse();
+        assert_eq!(decode(&image).unwrap(), text);
+        // modules of the bottom right corner, they're codewords corrected by Reed-Solomon
+        let width = image.width;
+        let mut image = render(text, 3);
+        for y in width - 21..width - 12 {
+            for x in width - 21..width - 12 {
+                image.luma[y * width + x] ^= 255
+            }
+        }
+        assert_eq!(decode(&image).unwrap(), text);
+    }
+
+    #[test]
+    fn no_code() {
+        let blank = Image { width: 100, height: 100, luma: vec![255; 10000] };
+        assert_eq!(decode(&blank).err().unwrap().cause, "no QR code found");
+        let noise = Image { width: 100, height: 100, luma: (0..10000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect() };
+        assert!(decode(&noise).is_err());
+        assert!(decode(&Image { width: 1, height: 1, luma: vec![0] }).is_err());
+        // finders of a code without the rest of it
+        let code = QrCode::encode(b"otpauth://totp/simtotp:john?secret=JBSWY3DPEHPK3PXP").unwrap();
+        let (size, width) = (code.size as isize, (code.size + 8) * 2);
+        let finder = |x: isize, y: isize| (x < 8 && (y < 8 || y >= size - 8)) || (y < 8 && x >= size - 8);
+        let luma = (0..width * width).map(|i| {
+            let (x, y) = ((i % width / 2) as isize - 4, (i / width / 2) as isize - 4);
+            if finder(x, y) && code.dark(x, y) { 0 } else { 255 }
+        }).collect();
+        assert!(decode(&Image { width, height: width, luma }).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+use std::io;
+
+/// Fills the buffer with random bytes provided by the OS.
+#[cfg(unix)]
+pub fn fill(buf: &mut [u8]) -> io::Result<()> {
+    use std::io::Read;
+    std::fs::File::open("/dev/urandom")?.read_exact(buf)
+}
+
+/// Fills the buffer with random bytes of the system preferred RNG of Windows.
+#[cfg(windows)]
+pub fn fill(buf: &mut [u8]) -> io::Result<()> {
+    use std::{ffi::c_void, ptr};
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 0x0000_0002;
+    #[link(name = "bcrypt")]
+    unsafe extern "system" {
+        fn BCryptGenRandom(algorithm: *mut c_void, buffer: *mut u8, len: u32, flags: u32) -> i32;
+    }
+    for chunk in buf.chunks_mut(u32::MAX as usize) {
+        // no algorithm handle is needed for the system preferred RNG
+        let status = unsafe { BCryptGenRandom(ptr::null_mut(), chunk.as_mut_ptr(), chunk.len() as u32, BCRYPT_USE_SYSTEM_PREFERRED_RNG) };
+        if status != 0 {
+            return Err(io::Error::other(format!("BCryptGenRandom failed with NTSTATUS {status:#x}")))
+        }
+    }
+    Ok(())
+}
+
+/// No OS random source is known, so nothing gets encrypted rather than using weak salts and nonces.
+#[cfg(not(any(unix, windows)))]
+pub fn fill(_buf: &mut [u8]) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no OS random source on the platform"))
+}
@@ -0,0 +1,143 @@
+/*
+scrypt password based key derivation as described in RFC 7914
+
+    B = PBKDF2-HMAC-SHA256(P, S, 1, p * 128 * r)
+    for i = 0 to p - 1
+        B_i = scryptROMix(r, B_i, N)
+    DK = PBKDF2-HMAC-SHA256(P, B, 1, dkLen)
+
+    scryptROMix(r, B, N):
+        X = B
+        for i = 0 to N - 1
+            V_i = X
+            X = scryptBlockMix(X)
+        for i = 0 to N - 1
+            j = Integerify(X) mod N     // the first word of the last 64 bytes block
+            X = scryptBlockMix(X xor V_j)
+        return X
+
+    scryptBlockMix(B_0 .. B_2r-1):     // 64 bytes blocks
+        X = B_2r-1
+        for i = 0 to 2r - 1
+            X = Salsa20/8(X xor B_i), Y_i = X
+        return Y_0, Y_2, ... Y_2r-2, Y_1, Y_3, ... Y_2r-1
+*/
+use crate::{TOTPError, Sha256, pbkdf2::pbkdf2_hmac};
+
+/// memory of ROMix and of the blocks allowed for parameters coming from a file
+const MAX_MEMORY: usize = 1 << 30;
+/// N * r * p allowed for parameters coming from a file, 64 times the one of Aegis
+const MAX_WORK: u64 = 1 << 24;
+
+/// Derives a key of the `out` length, N has to be a power of 2 greater than 1.
+pub fn scrypt(password: &[u8], salt: &[u8], n: u64, r: u32, p: u32, out: &mut [u8]) -> Result<(), TOTPError> {
+    if n < 2 || !n.is_power_of_two() {
+        return Err(TOTPError::new("scrypt N has to be a power of 2"))
+    }
+    let (r, p) = (r as usize, p as usize);
+    if r == 0 || p == 0 || (r as u64).saturating_mul(p as u64) >= 1 << 30 {
+        return Err(TOTPError::new("invalid scrypt r or p"))
+    }
+    let block_len = r.saturating_mul(128);
+    let n = usize::try_from(n).ok().filter(|&n| n.saturating_mul(block_len) <= MAX_MEMORY && p.saturating_mul(block_len) <= MAX_MEMORY)
+        .ok_or_else(|| TOTPError::new("scrypt parameters require too much memory"))?;
+    if (n as u64).saturating_mul(r as u64).saturating_mul(p as u64) > MAX_WORK {
+        return Err(TOTPError::new("scrypt parameters require too much work"))
+    }
+
+    let mut b = vec![0u8; p * block_len];
+    pbkdf2_hmac::<Sha256>(password, salt, 1, &mut b);
+    let mut v = vec![0u32; n * block_len / 4];
+    for chunk in b.chunks_mut(block_len) {
+        ro_mix(chunk, n, &mut v)
+    }
+    pbkdf2_hmac::<Sha256>(password, &b, 1, out);
+    Ok(())
+}
+
+fn ro_mix(b: &mut [u8], n: usize, v: &mut [u32]) {
+    let words = b.len() / 4;
+    let mut x: Vec<u32> = b.chunks(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect();
+    let mut y = vec![0u32; words];
+    for i in 0..n {
+        v[i * words..(i + 1) * words].copy_from_slice(&x);
+        block_mix(&x, &mut y);
+        std::mem::swap(&mut x, &mut y)
+    }
+    for _ in 0..n {
+        let j = x[words - 16] as usize & (n - 1);
+        for (x, v) in x.iter_mut().zip(&v[j * words..(j + 1) * words]) {
+            *x ^= v
+        }
+        block_mix(&x, &mut y);
+        std::mem::swap(&mut x, &mut y)
+    }
+    for (b, x) in b.chunks_mut(4).zip(x) {
+        b.copy_from_slice(&x.to_le_bytes())
+    }
+}
+
+fn block_mix(b: &[u32], y: &mut [u32]) {
+    let blocks = b.len() / 16;
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[(blocks - 1) * 16..]);
+    for i in 0..blocks {
+        for (x, b) in x.iter_mut().zip(&b[i * 16..(i + 1) * 16]) {
+            *x ^= b
+        }
+        salsa20_8(&mut x);
+        // even blocks go to the first half, and odd ones to the second
+        let pos = (i / 2 + (i % 2) * blocks / 2) * 16;
+        y[pos..pos + 16].copy_from_slice(&x)
+    }
+}
+
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    let mut quarter = |a: usize, b: usize, c: usize, d: usize| {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    };
+    for _ in 0..4 {
+        // columns
+        quarter(0, 4, 8, 12);
+        quarter(5, 9, 13, 1);
+        quarter(10, 14, 2, 6);
+        quarter(15, 3, 7, 11);
+        // rows
+        quarter(0, 1, 2, 3);
+        quarter(5, 6, 7, 4);
+        quarter(10, 11, 8, 9);
+        quarter(15, 12, 13, 14);
+    }
+    for (b, x) in b.iter_mut().zip(x) {
+        *b = b.wrapping_add(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 7914 test vectors
+    #[test]
+    fn vectors() {
+        let mut key = [0u8; 64];
+        scrypt(b"", b"", 16, 1, 1, &mut key).unwrap();
+        assert_eq!(simweb::to_hex(&key), "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906");
+        scrypt(b"password", b"NaCl", 1024, 8, 16, &mut key).unwrap();
+        assert_eq!(simweb::to_hex(&key), "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640");
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut key = [0u8; 32];
+        assert_eq!(scrypt(b"", b"", 1000, 8, 1, &mut key).err().unwrap().cause, "scrypt N has to be a power of 2");
+        assert_eq!(scrypt(b"", b"", 1024, 0, 1, &mut key).err().unwrap().cause, "invalid scrypt r or p");
+        assert_eq!(scrypt(b"", b"", 1 << 30, 8, 1, &mut key).err().unwrap().cause, "scrypt parameters require too much memory");
+        assert_eq!(scrypt(b"", b"", 2, 1 << 20, 1 << 9, &mut key).err().unwrap().cause, "scrypt parameters require too much memory");
+        assert_eq!(scrypt(b"", b"", 1 << 16, 8, 64, &mut key).err().unwrap().cause, "scrypt parameters require too much work");
+    }
+}
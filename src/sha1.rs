@@ -7,100 +7,125 @@ const H2: u32 = 0x98BADCFE;
 const H3: u32 = 0x10325476;
 const H4: u32 = 0xC3D2E1F0;
 
-pub struct Sha1;
+/// SHA-1 hasher consuming the data incrementally.
+#[derive(Clone)]
+pub struct Sha1 {
+    state: [u32; 5],
+    // a partial block waiting for more data
+    buffer: [u8; 64],
+    buffered: usize,
+    // the total message length in bytes
+    length: u64,
+}
 
 impl Sha1 {
     /// Constructs a new `Sha1` hasher.
     pub fn new() -> Self {
-        Sha1 {}
+        Sha1 {
+            state: [H0, H1, H2, H3, H4],
+            buffer: [0u8; 64],
+            buffered: 0,
+            length: 0,
+        }
     }
 
-    /// Computes the SHA-1 hash of the input byte array which can be a string or str type.
-    pub fn hash(&mut self, key: &[u8]) -> [u8; 20] {
-        // Initialize variables to the SHA-1's initial hash values.
-        let (mut h0, mut h1, mut h2, mut h3, mut h4) = (H0, H1, H2, H3, H4);
-
-        // Pad our key
-        let msg = self.pad_message(key);
-
-        // Process each 512-bit chunk of the padded message.
-        for chunk in msg.chunks(64) {
-            // Get the message schedule and copies of our initial SHA-1 values.
-            let schedule = self.build_schedule(chunk);
-
-            let (mut a, mut b, mut c, mut d, mut e) = (h0, h1, h2, h3, h4);
-
-            // Main loop of the SHA-1 algorithm using predefind values based on primes numbers.
-            #[allow(clippy::needless_range_loop)]
-            for i in 0..80 {
-                let (f, k) = match i {
-                    0..=19 => ((b & c) | ((!b) & d), 0x5A827999),
-                    20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
-                    40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
-                    _ => (b ^ c ^ d, 0xCA62C1D6),
-                };
-
-                // Update the temporary variable and then update the hash values
-                // in a manner that enforces both diffusion and confusion. Note
-                // how the "scrambled" data trickles through the variables as we
-                // loop through.
-                let temp = a
-                    .rotate_left(5)
-                    .wrapping_add(f)
-                    .wrapping_add(e)
-                    .wrapping_add(k)
-                    .wrapping_add(schedule[i]);
-                e = d;
-                d = c;
-                c = b.rotate_left(30);
-                b = a;
-                a = temp;
-            }
+    /// Discards all data fed to the hasher.
+    pub fn reset(&mut self) {
+        *self = Sha1::new()
+    }
 
-            // Add the compressed chunk to the current hash value.
-            h0 = h0.wrapping_add(a);
-            h1 = h1.wrapping_add(b);
-            h2 = h2.wrapping_add(c);
-            h3 = h3.wrapping_add(d);
-            h4 = h4.wrapping_add(e);
+    /// Feeds the next portion of the message, complete 512-bit chunks get processed right away.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        if self.buffered > 0 {
+            let len = data.len().min(64 - self.buffered);
+            self.buffer[self.buffered..self.buffered + len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+            if self.buffered < 64 {
+                return
+            }
+            let block = self.buffer;
+            self.process_chunk(&block);
+            self.buffered = 0;
         }
-
-        // Produce the final hash value as a 20-byte array.
-        let mut hash = [0u8; 20];
-
-        hash[0..4].copy_from_slice(&h0.to_be_bytes());
-        hash[4..8].copy_from_slice(&h1.to_be_bytes());
-        hash[8..12].copy_from_slice(&h2.to_be_bytes());
-        hash[12..16].copy_from_slice(&h3.to_be_bytes());
-        hash[16..20].copy_from_slice(&h4.to_be_bytes());
-
-        hash
+        let mut chunks = data.chunks_exact(64);
+        for chunk in &mut chunks {
+            self.process_chunk(chunk);
+        }
+        let rest = chunks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
     }
 
-    /// Pads the input message according to SHA-1 specifications.
-    /// This includes appending a '1' bit followed by '0' bits and finally the message length.
-    fn pad_message(&self, input: &[u8]) -> Vec<u8> {
-        let mut bytes = input.to_vec();
-
+    /// Pads the message according to SHA-1 specifications and produces the hash.
+    /// The hasher gets reset after and can be used for a new message.
+    pub fn finalize(&mut self) -> [u8; 20] {
         // Save the original message length for appending below.
-        let original_bit_length = bytes.len() as u64 * 8;
+        let original_bit_length = self.length.wrapping_mul(8);
 
         // Append the '1' at the most most significant bit: 10000000
-        bytes.push(0x80);
+        // and pad with '0' bytes until the message's length in bits modules 512 is 448.
+        let pad_len = if self.buffered < 56 { 56 - self.buffered } else { 120 - self.buffered };
+        let mut padding = [0u8; 64];
+        padding[0] = 0x80;
+        self.update(&padding[..pad_len]);
+
+        // Append the original message length.
+        self.update(&original_bit_length.to_be_bytes());
 
-        // Pad with '0' bytes until the message's length in bits modules 512 is 448.
-        while (bytes.len() * 8) % 512 != 448 {
-            bytes.push(0);
+        // Produce the final hash value as a 20-byte array.
+        let mut hash = [0u8; 20];
+        for (i, h) in self.state.iter().enumerate() {
+            hash[i * 4..i * 4 + 4].copy_from_slice(&h.to_be_bytes());
         }
+        self.reset();
 
-        // Append the original message length.
-        bytes.extend_from_slice(&original_bit_length.to_be_bytes());
+        hash
+    }
+
+    /// Processes a 512-bit chunk of the message.
+    fn process_chunk(&mut self, chunk: &[u8]) {
+        // Get the message schedule and copies of our current SHA-1 values.
+        let schedule = self.build_schedule(chunk);
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+
+        // Main loop of the SHA-1 algorithm using predefind values based on primes numbers.
+        #[allow(clippy::needless_range_loop)]
+        for i in 0..80 {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | ((!b) & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            // Update the temporary variable and then update the hash values
+            // in a manner that enforces both diffusion and confusion. Note
+            // how the "scrambled" data trickles through the variables as we
+            // loop through.
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(schedule[i]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
 
-        bytes
+        // Add the compressed chunk to the current hash value.
+        for (h, v) in self.state.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
     }
 
     /// Builds the message schedule array from a 512-bit chunk.
-    fn build_schedule(&mut self, chunk: &[u8]) -> [u32; 80] {
+    fn build_schedule(&self, chunk: &[u8]) -> [u32; 80] {
         let mut schedule = [0u32; 80];
 
         // Initialize the first 16 words in the array from the chunk.
@@ -116,4 +141,40 @@ impl Sha1 {
 
         schedule
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hmac::Digest;
+
+    const MESSAGE_448: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    const MESSAGE_896: &[u8] =
+        b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    /// FIPS 180 examples
+    #[test]
+    fn known_answers() {
+        let hash = |data: &[u8]| simweb::to_hex(&Sha1::digest(data));
+        assert_eq!(hash(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hash(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hash(MESSAGE_448), "84983e441c3bd26ebaae4aa1f95129e5e54670f1");
+        assert_eq!(hash(MESSAGE_896), "a49b2446a02c645bf419f995b67091253a04a259");
+    }
+
+    /// the message split at every offset, across the block boundary and the padding one
+    #[test]
+    fn split_updates() {
+        let mut hasher = Sha1::new();
+        for split in 0..=MESSAGE_896.len() {
+            hasher.update(&MESSAGE_896[..split]);
+            hasher.update(&MESSAGE_896[split..]);
+            assert_eq!(simweb::to_hex(&hasher.finalize()), "a49b2446a02c645bf419f995b67091253a04a259", "split at {split}");
+        }
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let expected = Sha1::digest(&data);
+        for size in [1, 3, 55, 56, 63, 64, 65, 127, 128, 129] {
+            data.chunks(size).for_each(|chunk| hasher.update(chunk));
+            assert_eq!(hasher.finalize(), expected, "chunks of {size}");
+        }
+    }
+}
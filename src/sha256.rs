@@ -0,0 +1,189 @@
+use std::convert::TryInto;
+// SHA-256 hashing algorithm initial hash values.
+// These constants are derived from the fractional parts of the square roots of the first eight primes.
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// Round constants, the fractional parts of the cube roots of the first 64 primes.
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 hasher consuming the data incrementally.
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    // a partial block waiting for more data
+    buffer: [u8; 64],
+    buffered: usize,
+    // the total message length in bytes
+    length: u64,
+}
+
+impl Sha256 {
+    /// Constructs a new `Sha256` hasher.
+    pub fn new() -> Self {
+        Sha256 {
+            state: H,
+            buffer: [0u8; 64],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    /// Discards all data fed to the hasher.
+    pub fn reset(&mut self) {
+        *self = Sha256::new()
+    }
+
+    /// Feeds the next portion of the message, complete 512-bit chunks get processed right away.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        if self.buffered > 0 {
+            let len = data.len().min(64 - self.buffered);
+            self.buffer[self.buffered..self.buffered + len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+            if self.buffered < 64 {
+                return
+            }
+            let block = self.buffer;
+            self.process_chunk(&block);
+            self.buffered = 0;
+        }
+        let mut chunks = data.chunks_exact(64);
+        for chunk in &mut chunks {
+            self.process_chunk(chunk);
+        }
+        let rest = chunks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    /// Pads the message according to SHA-256 specifications and produces the hash.
+    /// The hasher gets reset after and can be used for a new message.
+    pub fn finalize(&mut self) -> [u8; 32] {
+        // Save the original message length for appending below.
+        let original_bit_length = self.length.wrapping_mul(8);
+
+        // Append the '1' at the most most significant bit: 10000000
+        // and pad with '0' bytes until the message's length in bits modules 512 is 448.
+        let pad_len = if self.buffered < 56 { 56 - self.buffered } else { 120 - self.buffered };
+        let mut padding = [0u8; 64];
+        padding[0] = 0x80;
+        self.update(&padding[..pad_len]);
+
+        // Append the original message length.
+        self.update(&original_bit_length.to_be_bytes());
+
+        // Produce the final hash value as a 32-byte array.
+        let mut hash = [0u8; 32];
+        for (i, h) in self.state.iter().enumerate() {
+            hash[i * 4..i * 4 + 4].copy_from_slice(&h.to_be_bytes());
+        }
+        self.reset();
+
+        hash
+    }
+
+    /// Processes a 512-bit chunk of the message.
+    fn process_chunk(&mut self, chunk: &[u8]) {
+        let schedule = self.build_schedule(chunk);
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = self.state;
+
+        // Compression function main loop.
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ ((!e) & g);
+            let temp1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(schedule[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        // Add the compressed chunk to the current hash value.
+        for (h, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    /// Builds the message schedule array from a 512-bit chunk.
+    fn build_schedule(&self, chunk: &[u8]) -> [u32; 64] {
+        let mut schedule = [0u32; 64];
+
+        // Initialize the first 16 words in the array from the chunk.
+        for (i, block) in chunk.chunks(4).enumerate() {
+            schedule[i] = u32::from_be_bytes(block.try_into().unwrap());
+        }
+
+        // Extend the first 16 words into the remaining 48 words of the schedule array.
+        for i in 16..64 {
+            let s0 = schedule[i - 15].rotate_right(7) ^ schedule[i - 15].rotate_right(18) ^ (schedule[i - 15] >> 3);
+            let s1 = schedule[i - 2].rotate_right(17) ^ schedule[i - 2].rotate_right(19) ^ (schedule[i - 2] >> 10);
+            schedule[i] = schedule[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[i - 7])
+                .wrapping_add(s1);
+        }
+
+        schedule
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hmac::Digest;
+
+    const MESSAGE_448: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    const MESSAGE_896: &[u8] =
+        b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    /// FIPS 180 examples
+    #[test]
+    fn known_answers() {
+        let hash = |data: &[u8]| simweb::to_hex(&Sha256::digest(data));
+        assert_eq!(hash(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hash(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hash(MESSAGE_448), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(hash(MESSAGE_896), "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1");
+    }
+
+    /// the message split at every offset, across the block boundary and the padding one
+    #[test]
+    fn split_updates() {
+        let mut hasher = Sha256::new();
+        for split in 0..=MESSAGE_896.len() {
+            hasher.update(&MESSAGE_896[..split]);
+            hasher.update(&MESSAGE_896[split..]);
+            assert_eq!(simweb::to_hex(&hasher.finalize()), "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1", "split at {split}");
+        }
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let expected = Sha256::digest(&data);
+        for size in [1, 3, 55, 56, 63, 64, 65, 127, 128, 129] {
+            data.chunks(size).for_each(|chunk| hasher.update(chunk));
+            assert_eq!(hasher.finalize(), expected, "chunks of {size}");
+        }
+    }
+}
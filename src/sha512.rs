@@ -0,0 +1,202 @@
+use std::convert::TryInto;
+// SHA-512 hashing algorithm initial hash values.
+// These constants are derived from the fractional parts of the square roots of the first eight primes.
+const H: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+// Round constants, the fractional parts of the cube roots of the first 80 primes.
+const K: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc,
+    0x3956c25bf348b538, 0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118,
+    0xd807aa98a3030242, 0x12835b0145706fbe, 0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235, 0xc19bf174cf692694,
+    0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5,
+    0x983e5152ee66dfab, 0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2, 0xd5a79147930aa725, 0x06ca6351e003826f, 0x142929670a0e6e70,
+    0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed, 0x53380d139d95b3df,
+    0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30,
+    0xd192e819d6ef5218, 0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8, 0x1e376c085141ab53, 0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373, 0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b,
+    0xca273eceea26619c, 0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178,
+    0x06f067aa72176fba, 0x0a637dc5a2c898a6, 0x113f9804bef90dae, 0x1b710b35131c471b,
+    0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc, 0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+/// SHA-512 hasher consuming the data incrementally.
+#[derive(Clone)]
+pub struct Sha512 {
+    state: [u64; 8],
+    // a partial block waiting for more data
+    buffer: [u8; 128],
+    buffered: usize,
+    // the total message length in bytes
+    length: u128,
+}
+
+impl Sha512 {
+    /// Constructs a new `Sha512` hasher.
+    pub fn new() -> Self {
+        Sha512 {
+            state: H,
+            buffer: [0u8; 128],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    /// Discards all data fed to the hasher.
+    pub fn reset(&mut self) {
+        *self = Sha512::new()
+    }
+
+    /// Feeds the next portion of the message, complete 1024-bit chunks get processed right away.
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u128);
+        if self.buffered > 0 {
+            let len = data.len().min(128 - self.buffered);
+            self.buffer[self.buffered..self.buffered + len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+            if self.buffered < 128 {
+                return
+            }
+            let block = self.buffer;
+            self.process_chunk(&block);
+            self.buffered = 0;
+        }
+        let mut chunks = data.chunks_exact(128);
+        for chunk in &mut chunks {
+            self.process_chunk(chunk);
+        }
+        let rest = chunks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    /// Pads the message according to SHA-512 specifications and produces the hash.
+    /// The hasher gets reset after and can be used for a new message.
+    pub fn finalize(&mut self) -> [u8; 64] {
+        // Save the original message length for appending below.
+        let original_bit_length = self.length.wrapping_mul(8);
+
+        // Append the '1' at the most most significant bit: 10000000
+        // and pad with '0' bytes until the message's length in bits modules 1024 is 896.
+        let pad_len = if self.buffered < 112 { 112 - self.buffered } else { 240 - self.buffered };
+        let mut padding = [0u8; 128];
+        padding[0] = 0x80;
+        self.update(&padding[..pad_len]);
+
+        // Append the original message length.
+        self.update(&original_bit_length.to_be_bytes());
+
+        // Produce the final hash value as a 64-byte array.
+        let mut hash = [0u8; 64];
+        for (i, h) in self.state.iter().enumerate() {
+            hash[i * 8..i * 8 + 8].copy_from_slice(&h.to_be_bytes());
+        }
+        self.reset();
+
+        hash
+    }
+
+    /// Processes a 1024-bit chunk of the message.
+    fn process_chunk(&mut self, chunk: &[u8]) {
+        let schedule = self.build_schedule(chunk);
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = self.state;
+
+        // Compression function main loop.
+        for i in 0..80 {
+            let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+            let ch = (e & f) ^ ((!e) & g);
+            let temp1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(schedule[i]);
+            let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        // Add the compressed chunk to the current hash value.
+        for (h, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    /// Builds the message schedule array from a 1024-bit chunk.
+    fn build_schedule(&self, chunk: &[u8]) -> [u64; 80] {
+        let mut schedule = [0u64; 80];
+
+        // Initialize the first 16 words in the array from the chunk.
+        for (i, block) in chunk.chunks(8).enumerate() {
+            schedule[i] = u64::from_be_bytes(block.try_into().unwrap());
+        }
+
+        // Extend the first 16 words into the remaining 64 words of the schedule array.
+        for i in 16..80 {
+            let s0 = schedule[i - 15].rotate_right(1) ^ schedule[i - 15].rotate_right(8) ^ (schedule[i - 15] >> 7);
+            let s1 = schedule[i - 2].rotate_right(19) ^ schedule[i - 2].rotate_right(61) ^ (schedule[i - 2] >> 6);
+            schedule[i] = schedule[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[i - 7])
+                .wrapping_add(s1);
+        }
+
+        schedule
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hmac::Digest;
+
+    const MESSAGE_448: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    const MESSAGE_896: &[u8] =
+        b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    /// FIPS 180 examples
+    #[test]
+    fn known_answers() {
+        let hash = |data: &[u8]| simweb::to_hex(&Sha512::digest(data));
+        assert_eq!(hash(b"abc"), "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f");
+        assert_eq!(hash(b""), "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e");
+        assert_eq!(hash(MESSAGE_448), "204a8fc6dda82f0a0ced7beb8e08a41657c16ef468b228a8279be331a703c33596fd15c13b1b07f9aa1d3bea57789ca031ad85c7a71dd70354ec631238ca3445");
+        assert_eq!(hash(MESSAGE_896), "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909");
+    }
+
+    /// the message split at every offset, across the block boundary and the padding one
+    #[test]
+    fn split_updates() {
+        let mut hasher = Sha512::new();
+        for split in 0..=MESSAGE_896.len() {
+            hasher.update(&MESSAGE_896[..split]);
+            hasher.update(&MESSAGE_896[split..]);
+            assert_eq!(simweb::to_hex(&hasher.finalize()), "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909", "split at {split}");
+        }
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 7) as u8).collect();
+        let expected = Sha512::digest(&data);
+        for size in [1, 3, 63, 64, 65, 111, 112, 127, 128, 129, 255, 256] {
+            data.chunks(size).for_each(|chunk| hasher.update(chunk));
+            assert_eq!(hasher.finalize(), expected, "chunks of {size}");
+        }
+    }
+}
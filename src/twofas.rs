@@ -0,0 +1,123 @@
+/*
+2FAS Authenticator backup (.2fas file)
+
+    {
+        "services": [
+            {
+                "name": "service name",
+                "secret": BASE32,
+                "otp": {"label": "label", "account": "account", "issuer": "provider", "digits": 6, "period": 30,
+                    "algorithm": "SHA1", "tokenType": "TOTP", "counter": 0},       // TOTP, HOTP, STEAM
+                "groupId": ID
+            }
+        ],
+        "groups": [{"id": ID, "name": "group name"}],
+        "schemaVersion": 4,
+        "servicesEncrypted": "CIPHER:SALT:IV"   // base64 parts instead of services of a password protected backup
+    }
+
+    a protected backup is AES-256-GCM encrypted services JSON, the key is PBKDF2-HMAC-SHA256 of the password
+    with 10000 iterations, the tag ends the cipher text
+*/
+use crate::{TOTPError, Sha256, aes256gcm, pbkdf2::pbkdf2_hmac, migration::base64_decode, account::Account,
+    import::{self, Entry, Importer, Object, text, num, object, array, objects}};
+use simjson::JsonData;
+use std::collections::HashMap;
+
+const ITERATIONS: u32 = 10_000;
+
+pub struct TwoFas;
+
+impl Importer for TwoFas {
+    fn format(&self) -> &'static str {
+        "2FAS"
+    }
+
+    fn recognize(&self, json: &JsonData) -> bool {
+        matches!(json, JsonData::Data(backup) if backup.contains_key("services") && backup.contains_key("schemaVersion"))
+    }
+
+    fn entries(&self, json: &JsonData, password: &str) -> Result<Vec<Entry>, TOTPError> {
+        let JsonData::Data(backup) = json else {
+            return Err(TOTPError::new("not a 2FAS backup"))
+        };
+        let group_names: HashMap<&str, &str> = objects(array(backup, "groups"))
+            .filter_map(|group| Some((text(group, "id")?, text(group, "name")?))).collect();
+        let decrypted;
+        let services = match text(backup, "servicesEncrypted") {
+            Some(encrypted) => {
+                decrypted = decrypt(encrypted, password)?;
+                match &decrypted {
+                    JsonData::Arr(services) => services.as_slice(),
+                    _ => return Err(TOTPError::new("the decrypted services aren't a JSON array")),
+                }
+            }
+            None => array(backup, "services"),
+        };
+        Ok(objects(services).map(|service| parse_service(service, &group_names)).collect())
+    }
+}
+
+fn decrypt(encrypted: &str, password: &str) -> Result<JsonData, TOTPError> {
+    if password.is_empty() {
+        return Err(TOTPError::new("the backup is encrypted, a password is required"))
+    }
+    let parts: Vec<_> = encrypted.split(':').map(base64_decode).collect();
+    let [Some(sealed), Some(salt), Some(nonce)] = parts.as_slice() else {
+        return Err(TOTPError::new("the encrypted services are malformed"))
+    };
+    let nonce: &[u8; aes256gcm::NONCE_LEN] = nonce.as_slice().try_into().map_err(|_| TOTPError::new("invalid nonce"))?;
+    let mut key = [0u8; aes256gcm::KEY_LEN];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, ITERATIONS, &mut key);
+    let plain = aes256gcm::open(&key, nonce, &[], sealed).ok_or_else(|| TOTPError::new("wrong backup password"))?;
+    Ok(simjson::parse(&String::from_utf8_lossy(&plain)))
+}
+
+fn parse_service(service: &Object, group_names: &HashMap<&str, &str>) -> Entry {
+    let groups = text(service, "groupId").and_then(|id| group_names.get(id)).map(|name| vec![name.to_string()])
+        .unwrap_or_default();
+    let otp = object(service, "otp");
+    let otp_text = |name| otp.and_then(|otp| text(otp, name));
+    let otp_num = |name| otp.and_then(|otp| num(otp, name));
+    let issuer = otp_text("issuer").or(text(service, "name")).map(str::to_string);
+    let name = otp_text("account").or(otp_text("label")).or(text(service, "name")).unwrap_or_default().to_string();
+    let account = import::account(otp_text("tokenType").unwrap_or("totp"), text(service, "secret").unwrap_or_default(),
+        otp_text("algorithm"), otp_num("digits"), otp_num("period"), otp_num("counter"))
+        .map(|account| Account { issuer: issuer.clone(), ..account });
+    Entry { groups, issuer, name, account }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{Algorithm, Encoding};
+
+    const PLAIN: &str = r#"{"schemaVersion":4,"groups":[{"id":"g1","name":"Work"}],"services":[{"name":"GitHub","secret":"JBSWY3DPEHPK3PXP","otp":{"account":"john","issuer":"GitHub","digits":8,"period":60,"algorithm":"SHA512","tokenType":"TOTP"},"groupId":"g1"},{"name":"Bank","secret":"GEZDGNBVGY3TQOJQ","otp":{"label":"jane","tokenType":"HOTP","counter":7}},{"name":"Steam","secret":"MFRGGZDFMZTWQ2LK","otp":{"tokenType":"STEAM"}}]}"#;
+    /// one GitLab mary service encrypted by the password "test"
+    const ENCRYPTED: &str = r#"{"schemaVersion":4,"groups":[],"services":[],"servicesEncrypted":"MGqMe4ji1aaCiI0GKfsk0GAHW2tRhBvZ66dLDK1EwcSdfVIiRdvMlFSijnmbrDJWdOTWWMnbIE0AW9Lu1NB9WEg4Dw8FuMACuSNpVot0KMKVCBLTA9Y+4beofRbMRMV5or2lBg00dFV0pYuXrFbbSKvtjGFLzZ5IKYZEoKT0eZY0o/LOy3TbuWjz17cNZk/KrZ1UVJ4=:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=:AAECAwQFBgcICQoL"}"#;
+
+    #[test]
+    fn plain_backup() {
+        let entries = TwoFas.entries(&simjson::parse(PLAIN), "").unwrap();
+        let [john, jane, steam] = &entries[..] else { panic!("3 entries expected") };
+        assert_eq!((john.groups.as_slice(), john.issuer.as_deref(), john.name.as_str()), (&["Work".to_string()][..], Some("GitHub"), "john"));
+        let account = john.account.as_ref().unwrap();
+        assert_eq!((account.algorithm, account.digits, account.period), (Algorithm::Sha512, 8, 60));
+        assert_eq!((jane.issuer.as_deref(), jane.name.as_str(), jane.account.as_ref().unwrap().counter), (Some("Bank"), "jane", Some(7)));
+        assert!(jane.groups.is_empty());
+        assert_eq!((steam.name.as_str(), steam.account.as_ref().unwrap().encoding), ("Steam", Encoding::Steam));
+    }
+
+    #[test]
+    fn encrypted_backup() {
+        let entries = TwoFas.entries(&simjson::parse(ENCRYPTED), "test").unwrap();
+        let [mary] = &entries[..] else { panic!("1 entry expected") };
+        assert_eq!((mary.issuer.as_deref(), mary.name.as_str()), (Some("GitLab"), "mary"));
+        assert_eq!(mary.account.as_ref().unwrap().secret, "JBSWY3DPEHPK3PXP");
+        for (password, cause) in [("", "the backup is encrypted, a password is required"), ("Test", "wrong backup password")] {
+            assert_eq!(TwoFas.entries(&simjson::parse(ENCRYPTED), password).err().unwrap().cause, cause);
+        }
+        let malformed = ENCRYPTED.replace(":AAECAwQFBgcICQoL", "");
+        assert_eq!(TwoFas.entries(&simjson::parse(&malformed), "test").err().unwrap().cause, "the encrypted services are malformed");
+    }
+}